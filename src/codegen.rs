@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use crate::parser::*;
+use crate::types::Type;
 
 pub struct Code {
     code: String,
@@ -22,10 +24,6 @@ impl Code {
         self.code.push('\n');
     }
 
-    pub fn to_string(&self) -> String {
-        self.code.clone()
-    }
-
     pub fn append(&mut self, other: Self) {
         self.code.push_str(&other.code);
     }
@@ -36,10 +34,36 @@ impl Code {
     }
 }
 
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.code)
+    }
+}
+
+#[derive(Clone)]
+pub enum Symbol {
+    /// A stack slot at the given offset from `%rbp`
+    Local(i64),
+    /// A variable with static storage, addressed through its label
+    Global(String),
+}
+
+impl Symbol {
+    /// The memory operand that refers to the symbol's storage
+    pub fn operand(&self) -> String {
+        match self {
+            Symbol::Local(offset) => format!("{}(%rbp)", offset),
+            Symbol::Global(label) => format!("{}(%rip)", label),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Scope {
     pub parent: Option<Box<Scope>>,
-    pub symbols: HashMap<String, i64>,
+    pub symbols: HashMap<String, Symbol>,
+    /// Offset from `%rbp` of the lowest stack slot in use. Child scopes
+    /// start where their parent left off so their slots never overlap.
     pub stack_index: i64,
 }
 
@@ -48,53 +72,183 @@ impl Scope {
         Self {
             parent: None,
             symbols: HashMap::new(),
-            stack_index: -4,
+            stack_index: 0,
         }
     }
 
     pub fn from_parent(parent: Scope) -> Self {
+        let stack_index = parent.stack_index;
         Self {
             parent: Some(Box::new(parent)),
             symbols: HashMap::new(),
-            stack_index: -4,
+            stack_index,
         }
     }
 
-    pub fn contains_symbol(&self, name: &str) -> bool {
-        if self.symbols.contains_key(name) {
-            true
-        } else if let Some(parent) = &self.parent {
-            parent.contains_symbol(name)
-        } else {
-            false
+    /// Declares `name` in this scope, shadowing any outer declaration, and
+    /// returns its offset from `%rbp`.
+    pub fn add_symbol(&mut self, name: String, symbol_type: Type) -> i64 {
+        if self.symbols.contains_key(&name) {
+            panic!("Redeclaration of {}", name);
         }
+        self.stack_index -= symbol_type.size();
+        // Round down, away from %rbp, to the alignment of the type
+        self.stack_index = self.stack_index.div_euclid(symbol_type.align()) * symbol_type.align();
+        self.symbols.insert(name, Symbol::Local(self.stack_index));
+        self.stack_index
     }
 
-    pub fn add_symbol(&mut self, name: String) {
-        assert!(!self.contains_symbol(&name));
-        self.symbols.insert(name, self.stack_index);
-        self.stack_index -= 4;
+    /// Declares `name` as referring to the static storage at `label`. File
+    /// scope variables can be declared many times, so this never fails.
+    pub fn add_global(&mut self, name: String, label: String) {
+        self.symbols.insert(name, Symbol::Global(label));
     }
 
-    pub fn get_symbol(&self, name: &str) -> i64 {
-        if let Some(offset) = self.symbols.get(name) {
-            *offset
+    pub fn get_symbol(&self, name: &str) -> &Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            symbol
+        } else if let Some(parent) = &self.parent {
+            parent.get_symbol(name)
         } else {
             panic!("Symbol {} not found", name);
         }
     }
 }
 
+/// Escapes `bytes` so that they can be written inside a quoted assembler
+/// string.
+fn escape_string(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &byte in bytes {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+/// The value of the initializer of a variable with static storage, which
+/// semantic analysis has checked to be constant
+fn constant_initializer(initializer: &Expression) -> i64 {
+    evaluate_constant(initializer).expect("Static initializer is not constant")
+}
+
+/// The absolute value of a constant divisor of a value of type
+/// `value_type`, as an unsigned number
+fn divisor_magnitude(divisor: i64, value_type: &Type) -> u64 {
+    if value_type.is_signed() {
+        divisor.unsigned_abs()
+    } else {
+        divisor as u64
+    }
+}
+
+/// Whether `x % divisor` can be computed without a division: always for
+/// powers of two, and with a multiplication for values of at most 32 bits.
+fn can_lower_remainder(divisor: i64, value_type: &Type) -> bool {
+    let magnitude = divisor_magnitude(divisor, value_type);
+    magnitude != 0 && (magnitude.is_power_of_two() || value_type.size() <= 4)
+}
+
+/// Registers used to pass the first integer arguments, in order
+const ARGUMENT_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// A variable with static storage duration: a global or a `static` local
+struct StaticVariable {
+    label: String,
+    var_type: Type,
+    linkage: Option<Linkage>,
+    initializer: Option<i64>,
+    /// Whether this translation unit defines the variable, which is only
+    /// false when every declaration of it is `extern`. Definitions without
+    /// an initializer are tentative and end up zero-initialized.
+    defined: bool,
+}
+
+/// Where `break` and `continue` jump to inside the innermost loop or
+/// `switch`. A `switch` has no `continue` label of its own.
+struct LoopLabels {
+    break_label: String,
+    continue_label: Option<String>,
+}
+
+/// Labels of the `case` and `default` statements in a `switch` body
+struct SwitchLabels {
+    cases: HashMap<i64, String>,
+    default: Option<String>,
+}
+
+/// Switches with fewer cases than this are lowered to a chain of compares
+const MIN_SEARCH_CASES: usize = 4;
+
+/// Adds the value of every `case` label in `stmt` to `values`, leaving out
+/// those of nested switches, and returns whether there is a `default`.
+fn collect_cases(stmt: &Statement, values: &mut Vec<i64>) -> bool {
+    match stmt {
+        Statement::Case(value, stmt) => {
+            values.push(constant_initializer(value));
+            collect_cases(stmt, values)
+        }
+        Statement::Default(stmt) => {
+            collect_cases(stmt, values);
+            true
+        }
+        Statement::Labeled(_, stmt) => collect_cases(stmt, values),
+        Statement::Block(statements) => {
+            let mut found = false;
+            for stmt in statements {
+                found |= collect_cases(stmt, values);
+            }
+            found
+        }
+        Statement::If(_, then, otherwise) => {
+            let found = collect_cases(then, values);
+            otherwise.as_ref().is_some_and(|otherwise| collect_cases(otherwise, values)) || found
+        }
+        Statement::While(_, body) | Statement::DoWhile(body, _) | Statement::For(_, _, _, body) => {
+            collect_cases(body, values)
+        }
+        _ => false,
+    }
+}
+
 pub struct CodeGenerator {
     pub code: Code,
+    /// Emitted into `.data` or `.bss` once all declarations have been seen
+    static_variables: Vec<StaticVariable>,
+    /// Labels and contents of string literals, emitted into `.rodata`
+    strings: Vec<(String, Vec<u8>)>,
     label_count: usize,
+    loops: Vec<LoopLabels>,
+    switches: Vec<SwitchLabels>,
+    /// Name of the function being generated, which qualifies its labels
+    function_name: String,
+    /// Labels of the jump tables of switches and the labels they jump to,
+    /// emitted into `.rodata`
+    jump_tables: Vec<(String, Vec<String>)>,
+    /// Bytes of stack needed by the locals of the current function
+    frame_size: i64,
+    /// Bytes pushed below the frame for temporaries, used to keep `%rsp`
+    /// 16-byte aligned at call sites
+    stack_depth: i64,
 }
 
 impl CodeGenerator {
     pub fn new() -> Self {
         Self {
             code: Code::new(),
+            static_variables: Vec::new(),
+            strings: Vec::new(),
             label_count: 0,
+            loops: Vec::new(),
+            switches: Vec::new(),
+            function_name: String::new(),
+            jump_tables: Vec::new(),
+            frame_size: 0,
+            stack_depth: 0,
         }
     }
 
@@ -107,24 +261,473 @@ impl CodeGenerator {
 
     pub fn generate(&mut self, program: Program) {
         let mut scope = Scope::new();
-        for func_decl in program.declarations {
-            self.generate_func_decl(&mut scope, func_decl);
+        self.code.add_asm_line(".text");
+        for declaration in program.declarations {
+            match declaration {
+                Declaration::Function(func_decl) => {
+                    if func_decl.body.is_some() {
+                        self.generate_func_decl(&mut scope, func_decl);
+                    }
+                }
+                Declaration::Variable(var_decl) => {
+                    self.declare_global_var(&mut scope, var_decl);
+                }
+                Declaration::Type(_) | Declaration::Typedef(..) => {}
+            }
+        }
+        self.generate_static_variables();
+        self.generate_strings();
+        self.generate_jump_tables();
+    }
+
+    fn generate_strings(&mut self) {
+        if self.strings.is_empty() {
+            return;
+        }
+        self.code.add_asm_line(".section .rodata");
+        for (label, bytes) in std::mem::take(&mut self.strings) {
+            self.code.add_label(label);
+            self.code.add_asm_line(&format!(".string \"{}\"", escape_string(&bytes)));
+        }
+    }
+
+    /// Entries are offsets of their targets from the start of the table,
+    /// which makes the tables position-independent: they need no dynamic
+    /// relocations when the program is linked as PIE.
+    fn generate_jump_tables(&mut self) {
+        if self.jump_tables.is_empty() {
+            return;
+        }
+        self.code.add_asm_line(".section .rodata");
+        self.code.add_asm_line(".align 4");
+        for (table, targets) in std::mem::take(&mut self.jump_tables) {
+            self.code.add_label(table.clone());
+            for target in targets {
+                self.code.add_asm_line(&format!(".long {}-{}", target, table));
+            }
+        }
+    }
+
+    fn declare_global_var(&mut self, scope: &mut Scope, var_decl: VariableDeclaration) {
+        let linkage = var_decl.linkage.expect("Linkage has not been resolved");
+        let initializer = var_decl.initializer.as_ref().map(constant_initializer);
+        // `extern int x = 1;` is still a definition
+        let defined = var_decl.storage_class != Some(StorageClass::Extern) || initializer.is_some();
+        self.add_static_variable(var_decl.name.clone(), &var_decl.var_type, linkage, initializer, defined);
+        scope.add_global(var_decl.name.clone(), var_decl.name);
+    }
+
+    /// Records a declaration of a variable with static storage, merging it
+    /// with earlier declarations of the same label, which semantic analysis
+    /// has checked to agree with it.
+    fn add_static_variable(
+        &mut self,
+        label: String,
+        var_type: &Type,
+        linkage: Linkage,
+        initializer: Option<i64>,
+        defined: bool,
+    ) {
+        if let Some(variable) = self.static_variables.iter_mut().find(|v| v.label == label) {
+            if initializer.is_some() {
+                variable.initializer = initializer;
+            }
+            variable.linkage = Some(linkage);
+            variable.defined |= defined;
+        } else {
+            self.static_variables.push(StaticVariable {
+                label,
+                var_type: var_type.clone(),
+                linkage: Some(linkage),
+                initializer,
+                defined,
+            });
+        }
+    }
+
+    fn generate_static_variables(&mut self) {
+        let mut data = Code::new();
+        let mut bss = Code::new();
+        for variable in self.static_variables.iter().filter(|v| v.defined) {
+            let value = variable.initializer.unwrap_or(0);
+            let section = if value == 0 { &mut bss } else { &mut data };
+            if variable.linkage == Some(Linkage::External) {
+                section.add_asm_line(&format!(".globl {}", variable.label));
+            }
+            let mut align = variable.var_type.align();
+            // The ABI gives arrays of 16 bytes or more at least 16-byte alignment
+            if matches!(variable.var_type, Type::Array(..)) && variable.var_type.size() >= 16 {
+                align = align.max(16);
+            }
+            section.add_asm_line(&format!(".align {}", align));
+            section.add_asm_line(&format!("{}:", variable.label));
+            match variable.var_type.size() {
+                _ if value == 0 => section.add_asm_line(&format!(".zero {}", variable.var_type.size())),
+                1 => section.add_asm_line(&format!(".byte {}", value as i8)),
+                2 => section.add_asm_line(&format!(".short {}", value as i16)),
+                4 => section.add_asm_line(&format!(".long {}", value as i32)),
+                _ => section.add_asm_line(&format!(".quad {}", value)),
+            }
+        }
+        if !data.code.is_empty() {
+            self.code.add_asm_line(".data");
+            self.code.append(data);
+        }
+        if !bss.code.is_empty() {
+            self.code.add_asm_line(".bss");
+            self.code.append(bss);
         }
     }
 
     fn generate_func_decl(&mut self, parent_scope: &mut Scope, func_decl: FunctionDeclaration) {
         let mut scope = Scope::from_parent(parent_scope.clone());
-        self.code.add_asm_line(&format!(".globl {}", func_decl.name));
-        self.code.add_asm_line(&format!("{}:", func_decl.name));
-        self.code.add_asm_line("push %rbp");
-        self.code.add_asm_line("mov %rsp, %rbp");
-        for stmt in func_decl.body {
+        // The frame size is only known once the whole body has been
+        // generated, so the body goes into its own buffer first.
+        let outer_code = std::mem::replace(&mut self.code, Code::new());
+        self.frame_size = 0;
+        self.function_name = func_decl.name.clone();
+        for (i, parameter) in func_decl.parameters.into_iter().enumerate() {
+            let param_type = parameter.param_type;
+            let offset = self.declare_local(&mut scope, parameter.name.unwrap(), param_type.clone());
+            let operand = format!("{}(%rbp)", offset);
+            if i < ARGUMENT_REGISTERS.len() {
+                self.code.add_asm_line(&format!("mov {}, %rax", ARGUMENT_REGISTERS[i]));
+            } else {
+                // The caller left the rest above our return address
+                let caller_offset = 16 + 8 * (i - ARGUMENT_REGISTERS.len());
+                self.code.add_asm_line(&format!("mov {}(%rbp), %rax", caller_offset));
+            }
+            self.store(&operand, &param_type);
+        }
+        for stmt in func_decl.body.unwrap() {
             self.generate_stmt(&mut scope, stmt);
         }
         self.code.add_asm_line("xor %rax, %rax");
         self.code.add_asm_line("mov %rbp, %rsp");
         self.code.add_asm_line("pop %rbp");
         self.code.add_asm_line("ret");
+        let body = std::mem::replace(&mut self.code, outer_code);
+
+        if func_decl.linkage == Some(Linkage::External) {
+            self.code.add_asm_line(&format!(".globl {}", func_decl.name));
+        }
+        self.code.add_asm_line(&format!("{}:", func_decl.name));
+        self.code.add_asm_line("push %rbp");
+        self.code.add_asm_line("mov %rsp, %rbp");
+        let frame_size = (self.frame_size + 15) / 16 * 16;
+        if frame_size > 0 {
+            self.code.add_asm_line(&format!("sub ${}, %rsp", frame_size));
+        }
+        self.code.append(body);
+    }
+
+    fn push(&mut self, register: &str) {
+        self.code.add_asm_line(&format!("push {}", register));
+        self.stack_depth += 8;
+    }
+
+    fn pop(&mut self, register: &str) {
+        self.code.add_asm_line(&format!("pop {}", register));
+        self.stack_depth -= 8;
+    }
+
+    fn declare_local(&mut self, scope: &mut Scope, name: String, var_type: Type) -> i64 {
+        let offset = scope.add_symbol(name, var_type);
+        self.frame_size = self.frame_size.max(-offset);
+        offset
+    }
+
+    // Integer values are always kept in %rax sign- or zero-extended to 64
+    // bits from the width of their type, depending on its signedness.
+    // Arithmetic can then be done on the whole register, with the result
+    // brought back to its type's width so that overflow wraps the same way
+    // it would at that width.
+
+    /// Loads a value of type `value_type` from `operand` into `%rax`. The
+    /// value of an array is the address of its first element, and structs
+    /// are also handled through their address.
+    fn load(&mut self, operand: &str, value_type: &Type) {
+        match value_type {
+            Type::Array(..) | Type::Struct(_) => self.code.add_asm_line(&format!("lea {}, %rax", operand)),
+            _ => match (value_type.size(), value_type.is_signed()) {
+                (1, true) => self.code.add_asm_line(&format!("movsbq {}, %rax", operand)),
+                (1, false) => self.code.add_asm_line(&format!("movzbq {}, %rax", operand)),
+                (2, true) => self.code.add_asm_line(&format!("movswq {}, %rax", operand)),
+                (2, false) => self.code.add_asm_line(&format!("movzwq {}, %rax", operand)),
+                (4, true) => self.code.add_asm_line(&format!("movslq {}, %rax", operand)),
+                // Writing %eax clears the upper half of %rax
+                (4, false) => self.code.add_asm_line(&format!("movl {}, %eax", operand)),
+                _ => self.code.add_asm_line(&format!("mov {}, %rax", operand)),
+            },
+        }
+    }
+
+    /// Stores the value in `%rax` to `operand`, truncating it to the size of
+    /// `value_type`.
+    fn store(&mut self, operand: &str, value_type: &Type) {
+        match value_type {
+            Type::Array(..) => panic!("Cannot assign to an array"),
+            Type::Struct(_) => {
+                // Copy from the address in %rax, widest chunks first
+                self.code.add_asm_line(&format!("lea {}, %rcx", operand));
+                let size = value_type.size();
+                let mut offset = 0;
+                while offset < size {
+                    let (chunk, register) = match size - offset {
+                        8.. => (8, "%rdx"),
+                        4..=7 => (4, "%edx"),
+                        2..=3 => (2, "%dx"),
+                        _ => (1, "%dl"),
+                    };
+                    self.code.add_asm_line(&format!("mov {}(%rax), {}", offset, register));
+                    self.code.add_asm_line(&format!("mov {}, {}(%rcx)", register, offset));
+                    offset += chunk;
+                }
+            }
+            _ => match value_type.size() {
+                1 => self.code.add_asm_line(&format!("movb %al, {}", operand)),
+                2 => self.code.add_asm_line(&format!("movw %ax, {}", operand)),
+                4 => self.code.add_asm_line(&format!("movl %eax, {}", operand)),
+                _ => self.code.add_asm_line(&format!("mov %rax, {}", operand)),
+            },
+        }
+    }
+
+    /// Truncates `%rax` to the width of `value_type` and extends it back.
+    fn normalize(&mut self, value_type: &Type) {
+        match (value_type.size(), value_type.is_signed()) {
+            (1, true) => self.code.add_asm_line("movsbq %al, %rax"),
+            (1, false) => self.code.add_asm_line("movzbq %al, %rax"),
+            (2, true) => self.code.add_asm_line("movswq %ax, %rax"),
+            (2, false) => self.code.add_asm_line("movzwq %ax, %rax"),
+            (4, true) => self.code.add_asm_line("movslq %eax, %rax"),
+            (4, false) => self.code.add_asm_line("mov %eax, %eax"),
+            _ => {}
+        }
+    }
+
+    /// Converts the value in `%rax` from type `from` to type `to`. Widening
+    /// without changing signedness is free because values are already
+    /// extended to 64 bits. Otherwise the value is re-extended, so that for
+    /// instance a negative `short` becomes a large `unsigned int`.
+    fn convert(&mut self, from: &Type, to: &Type) {
+        let from = from.decay();
+        if to.size() < from.size() || to.is_signed() != from.is_signed() {
+            self.normalize(to);
+        }
+    }
+
+    /// Replaces the value in `%rax` by its remainder modulo a constant, which
+    /// `can_lower_remainder` accepts. Like division in C, the remainder has
+    /// the sign of the dividend, and that of the divisor doesn't matter.
+    fn generate_constant_remainder(&mut self, divisor: i64, value_type: &Type) {
+        let magnitude = divisor_magnitude(divisor, value_type);
+        let signed = value_type.is_signed();
+        if magnitude == 1 {
+            self.code.add_asm_line("mov $0, %rax");
+            return;
+        }
+        if magnitude.is_power_of_two() {
+            let mask = magnitude - 1;
+            let mask_operand = if i32::try_from(mask).is_ok() {
+                format!("${}", mask)
+            } else {
+                self.code.add_asm_line(&format!("mov ${}, %rdx", mask));
+                "%rdx".to_string()
+            };
+            if signed {
+                // Negative values are biased by `mask` before masking and
+                // unbiased after, which rounds toward zero
+                self.code.add_asm_line("mov %rax, %rcx");
+                self.code.add_asm_line("sar $63, %rcx");
+                self.code.add_asm_line(&format!("shr ${}, %rcx", 64 - magnitude.trailing_zeros()));
+                self.code.add_asm_line("add %rcx, %rax");
+                self.code.add_asm_line(&format!("and {}, %rax", mask_operand));
+                self.code.add_asm_line("sub %rcx, %rax");
+            } else {
+                self.code.add_asm_line(&format!("and {}, %rax", mask_operand));
+            }
+            return;
+        }
+        // The dividend fits in 32 bits once its sign is taken away, so its
+        // quotient is floor(n * m / 2^shift) with m = ceil(2^shift / d) and
+        // shift = 32 + ceil(log2 d) (Granlund and Montgomery)
+        let shift = 32 + (64 - (magnitude - 1).leading_zeros());
+        let multiplier = (1u128 << shift).div_ceil(u128::from(magnitude));
+        self.push("%rax");
+        if signed {
+            self.code.add_asm_line("mov %rax, %rcx");
+            self.code.add_asm_line("sar $63, %rcx");
+            self.code.add_asm_line("xor %rcx, %rax");
+            self.code.add_asm_line("sub %rcx, %rax");
+        }
+        self.code.add_asm_line(&format!("mov ${}, %rcx", multiplier));
+        self.code.add_asm_line("mul %rcx");
+        if shift == 64 {
+            self.code.add_asm_line("mov %rdx, %rax");
+        } else {
+            self.code.add_asm_line(&format!("shrd ${}, %rdx, %rax", shift));
+        }
+        self.code.add_asm_line(&format!("mov ${}, %rcx", magnitude));
+        self.code.add_asm_line("imul %rax, %rcx");
+        self.pop("%rax");
+        if signed {
+            // Give the multiple of the divisor the sign of the dividend
+            self.code.add_asm_line("mov %rax, %rdx");
+            self.code.add_asm_line("sar $63, %rdx");
+            self.code.add_asm_line("xor %rdx, %rcx");
+            self.code.add_asm_line("sub %rdx, %rcx");
+        }
+        self.code.add_asm_line("sub %rcx, %rax");
+    }
+
+    /// Adds or subtracts one, or the pointee size for pointers, to the lvalue
+    /// `operand`. The value left in `%rax` is the old one if `postfix`.
+    fn generate_increment(&mut self, scope: &mut Scope, op: IncrementOperator, operand: Expression, postfix: bool) {
+        let operand_type = operand.expr_type().clone();
+        let step = operand_type.pointee().map_or(1, |target| target.size());
+        let instruction = match op {
+            IncrementOperator::Increment => "add",
+            IncrementOperator::Decrement => "sub",
+        };
+        self.generate_address(scope, operand);
+        self.code.add_asm_line("mov %rax, %rcx");
+        self.load("(%rcx)", &operand_type);
+        if postfix {
+            self.code.add_asm_line("mov %rax, %rdx");
+        }
+        self.code.add_asm_line(&format!("{} ${}, %rax", instruction, step));
+        self.normalize(&operand_type);
+        self.store("(%rcx)", &operand_type);
+        if postfix {
+            self.code.add_asm_line("mov %rdx, %rax");
+        }
+    }
+
+    /// Applies `op` to the left operand in `%rax` and the right one in `%rcx`,
+    /// leaving a result of type `result_type` in `%rax`. Semantic analysis
+    /// has converted integer operands to their common type, or promoted them
+    /// for shifts. Pointers compare as unsigned addresses.
+    fn generate_binary_operator(&mut self, op: BinaryOperator, left_type: &Type, right_type: &Type, result_type: &Type) {
+        let unsigned = !left_type.is_signed();
+        match op {
+            BinaryOperator::Plus | BinaryOperator::Minus => {
+                self.generate_additive(op, left_type, right_type);
+                self.normalize(result_type);
+            }
+            BinaryOperator::Times => {
+                self.code.add_asm_line("imul %rcx, %rax");
+                self.normalize(result_type);
+            }
+            BinaryOperator::Divide => {
+                if unsigned {
+                    self.code.add_asm_line("xor %rdx, %rdx");
+                    self.code.add_asm_line("div %rcx");
+                } else {
+                    self.code.add_asm_line("cqo");
+                    self.code.add_asm_line("idiv %rcx");
+                }
+                self.normalize(result_type);
+            }
+            BinaryOperator::Remainder => {
+                if unsigned {
+                    self.code.add_asm_line("xor %rdx, %rdx");
+                    self.code.add_asm_line("div %rcx");
+                } else {
+                    self.code.add_asm_line("cqo");
+                    self.code.add_asm_line("idiv %rcx");
+                }
+                self.code.add_asm_line("mov %rdx, %rax");
+            }
+            BinaryOperator::ShiftLeft => {
+                self.code.add_asm_line("shl %cl, %rax");
+                self.normalize(result_type);
+            }
+            // The value is already extended to 64 bits, so shifting the
+            // whole register brings in the right bits
+            BinaryOperator::ShiftRight => {
+                self.code.add_asm_line(if unsigned { "shr %cl, %rax" } else { "sar %cl, %rax" });
+            }
+            BinaryOperator::BitwiseAnd => self.code.add_asm_line("and %rcx, %rax"),
+            BinaryOperator::BitwiseXor => self.code.add_asm_line("xor %rcx, %rax"),
+            BinaryOperator::BitwiseOr => self.code.add_asm_line("or %rcx, %rax"),
+            BinaryOperator::EQ | BinaryOperator::NEQ | BinaryOperator::LT
+            | BinaryOperator::GT | BinaryOperator::LE | BinaryOperator::GE => {
+                let condition = match (op, unsigned) {
+                    (BinaryOperator::EQ, _) => "e",
+                    (BinaryOperator::NEQ, _) => "ne",
+                    (BinaryOperator::LT, false) => "l",
+                    (BinaryOperator::LT, true) => "b",
+                    (BinaryOperator::GT, false) => "g",
+                    (BinaryOperator::GT, true) => "a",
+                    (BinaryOperator::LE, false) => "le",
+                    (BinaryOperator::LE, true) => "be",
+                    (BinaryOperator::GE, false) => "ge",
+                    _ => "ae",
+                };
+                self.code.add_asm_line("cmp %rcx, %rax");
+                self.code.add_asm_line("mov $0, %rax");
+                self.code.add_asm_line(&format!("set{} %al", condition));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Adds or subtracts `%rcx` from `%rax`, scaling the integer operand by
+    /// the pointee size when the other one is a pointer.
+    fn generate_additive(&mut self, op: BinaryOperator, left_type: &Type, right_type: &Type) {
+        let instruction = match op {
+            BinaryOperator::Plus => "add",
+            BinaryOperator::Minus => "sub",
+            _ => unreachable!(),
+        };
+        match (left_type.pointee(), right_type.pointee()) {
+            (Some(left_target), Some(right_target)) => {
+                if op != BinaryOperator::Minus || left_target != right_target {
+                    panic!("Invalid operands {:?} and {:?} to {:?}", left_type, right_type, op);
+                }
+                self.code.add_asm_line("sub %rcx, %rax");
+                self.code.add_asm_line("cqo");
+                self.code.add_asm_line(&format!("mov ${}, %rcx", left_target.size()));
+                self.code.add_asm_line("idiv %rcx");
+            }
+            (Some(target), None) => {
+                self.code.add_asm_line(&format!("imul ${}, %rcx", target.size()));
+                self.code.add_asm_line(&format!("{} %rcx, %rax", instruction));
+            }
+            (None, Some(target)) => {
+                if op != BinaryOperator::Plus {
+                    panic!("Invalid operands {:?} and {:?} to {:?}", left_type, right_type, op);
+                }
+                self.code.add_asm_line(&format!("imul ${}, %rax", target.size()));
+                self.code.add_asm_line("add %rcx, %rax");
+            }
+            (None, None) => self.code.add_asm_line(&format!("{} %rcx, %rax", instruction)),
+        }
+    }
+
+    /// Leaves the address of an lvalue in `%rax`
+    fn generate_address(&mut self, scope: &mut Scope, expr: Expression) {
+        match expr.kind {
+            ExpressionKind::Variable(name) => {
+                let operand = scope.get_symbol(&name).operand();
+                self.code.add_asm_line(&format!("lea {}, %rax", operand));
+            }
+            ExpressionKind::UnaryOperation(UnaryOperator::Dereference, pointer) => {
+                self.generate_expr(scope, *pointer);
+            }
+            ExpressionKind::Member(base, name) => {
+                let Type::Struct(struct_type) = base.expr_type()
+                else { panic!("Member access on {:?}", base.expr_type()) };
+                let offset = struct_type.member(&name).expect("Unknown struct member").offset;
+                // The value of a struct is its address
+                self.generate_expr(scope, *base);
+                if offset != 0 {
+                    self.code.add_asm_line(&format!("add ${}, %rax", offset));
+                }
+            }
+            kind => panic!("Expression {:?} is not an lvalue", kind),
+        }
     }
 
     fn generate_stmt(&mut self, scope: &mut Scope, stmt: Statement) {
@@ -135,41 +738,306 @@ impl CodeGenerator {
                 self.code.add_asm_line("pop %rbp");
                 self.code.add_asm_line("ret");
             }
-            Statement::Declare(name, None) => {
-                self.code.add_asm_line("sub $4, %rsp");
-                scope.add_symbol(name);
-            }
-            Statement::Declare(name, Some(expr)) => {
-                let offset = scope.stack_index;
-                scope.add_symbol(name);
-                self.generate_expr(scope, expr);
-                self.code.add_asm_line("sub $4, %rsp");
-                self.code.add_asm_line(&format!("mov %eax, {}(%rbp)", offset));
+            Statement::Block(statements) => {
+                let mut block_scope = Scope::from_parent(scope.clone());
+                for stmt in statements {
+                    self.generate_stmt(&mut block_scope, stmt);
+                }
             }
+            Statement::Declare(var_decl) => match var_decl.storage_class {
+                None => {
+                    let var_type = var_decl.var_type;
+                    let offset = self.declare_local(scope, var_decl.name, var_type.clone());
+                    if let Some(expr) = var_decl.initializer {
+                        self.generate_expr(scope, expr);
+                        self.store(&format!("{}(%rbp)", offset), &var_type);
+                    }
+                }
+                Some(StorageClass::Static) => {
+                    // Static locals need a label that can't clash with other
+                    // functions' statics or with global symbols
+                    let label = format!("{}{}", var_decl.name, self.get_label());
+                    let initializer = var_decl.initializer.as_ref().map(constant_initializer);
+                    self.static_variables.push(StaticVariable {
+                        label: label.clone(),
+                        var_type: var_decl.var_type.clone(),
+                        linkage: None,
+                        initializer,
+                        defined: true,
+                    });
+                    scope.add_global(var_decl.name, label);
+                }
+                Some(StorageClass::Extern) => {
+                    let linkage = var_decl.linkage.expect("Linkage has not been resolved");
+                    self.add_static_variable(var_decl.name.clone(), &var_decl.var_type, linkage, None, false);
+                    scope.add_global(var_decl.name.clone(), var_decl.name);
+                }
+            },
+            Statement::DeclareType(_) | Statement::Typedef(..) => {}
             Statement::Expression(expr) => {
                 self.generate_expr(scope, expr);
             }
+            Statement::If(condition, then, None) => {
+                let end = self.get_label();
+                self.generate_expr(scope, condition);
+                self.code.add_asm_line("cmp $0, %rax");
+                self.code.add_asm_line(&format!("je {}", end));
+                self.generate_stmt(scope, *then);
+                self.code.add_label(end);
+            }
+            Statement::If(condition, then, Some(otherwise)) => {
+                let else_label = self.get_label();
+                let end = self.get_label();
+                self.generate_expr(scope, condition);
+                self.code.add_asm_line("cmp $0, %rax");
+                self.code.add_asm_line(&format!("je {}", else_label));
+                self.generate_stmt(scope, *then);
+                self.code.add_asm_line(&format!("jmp {}", end));
+                self.code.add_label(else_label);
+                self.generate_stmt(scope, *otherwise);
+                self.code.add_label(end);
+            }
+            Statement::While(condition, body) => {
+                let start = self.get_label();
+                let end = self.get_label();
+                self.code.add_label(start.clone());
+                self.generate_expr(scope, condition);
+                self.code.add_asm_line("cmp $0, %rax");
+                self.code.add_asm_line(&format!("je {}", end));
+                self.loops.push(LoopLabels {
+                    break_label: end.clone(),
+                    continue_label: Some(start.clone()),
+                });
+                self.generate_stmt(scope, *body);
+                self.loops.pop();
+                self.code.add_asm_line(&format!("jmp {}", start));
+                self.code.add_label(end);
+            }
+            Statement::DoWhile(body, condition) => {
+                let start = self.get_label();
+                let cond_label = self.get_label();
+                let end = self.get_label();
+                self.code.add_label(start.clone());
+                self.loops.push(LoopLabels {
+                    break_label: end.clone(),
+                    continue_label: Some(cond_label.clone()),
+                });
+                self.generate_stmt(scope, *body);
+                self.loops.pop();
+                self.code.add_label(cond_label);
+                self.generate_expr(scope, condition);
+                self.code.add_asm_line("cmp $0, %rax");
+                self.code.add_asm_line(&format!("jne {}", start));
+                self.code.add_label(end);
+            }
+            Statement::For(init, condition, step, body) => {
+                // The induction variable only lives as long as the loop
+                let mut loop_scope = Scope::from_parent(scope.clone());
+                let start = self.get_label();
+                let step_label = self.get_label();
+                let end = self.get_label();
+                if let Some(init) = init {
+                    self.generate_stmt(&mut loop_scope, *init);
+                }
+                self.code.add_label(start.clone());
+                if let Some(condition) = condition {
+                    self.generate_expr(&mut loop_scope, condition);
+                    self.code.add_asm_line("cmp $0, %rax");
+                    self.code.add_asm_line(&format!("je {}", end));
+                }
+                self.loops.push(LoopLabels {
+                    break_label: end.clone(),
+                    continue_label: Some(step_label.clone()),
+                });
+                self.generate_stmt(&mut loop_scope, *body);
+                self.loops.pop();
+                self.code.add_label(step_label);
+                if let Some(step) = step {
+                    self.generate_expr(&mut loop_scope, step);
+                }
+                self.code.add_asm_line(&format!("jmp {}", start));
+                self.code.add_label(end);
+            }
+            Statement::Switch(condition, body) => {
+                let switch_type = condition.expr_type().clone();
+                let end = self.get_label();
+                let mut values = Vec::new();
+                let has_default = collect_cases(&body, &mut values);
+                let mut cases: Vec<(i64, String)> = values.into_iter().map(|value| (value, self.get_label())).collect();
+                let default = has_default.then(|| self.get_label());
+                self.generate_expr(scope, condition);
+                // Order the cases the way the controlling expression compares
+                if switch_type.is_signed() {
+                    cases.sort_by_key(|(value, _)| *value);
+                } else {
+                    cases.sort_by_key(|(value, _)| *value as u64);
+                }
+                self.generate_case_dispatch(&cases, default.as_ref().unwrap_or(&end), switch_type.is_signed());
+                self.switches.push(SwitchLabels { cases: cases.into_iter().collect(), default });
+                self.loops.push(LoopLabels {
+                    break_label: end.clone(),
+                    continue_label: None,
+                });
+                self.generate_stmt(scope, *body);
+                self.loops.pop();
+                self.switches.pop();
+                self.code.add_label(end);
+            }
+            Statement::Case(value, stmt) => {
+                let label = self.switches.last().expect("case label not within a switch statement")
+                    .cases[&constant_initializer(&value)].clone();
+                self.code.add_label(label);
+                self.generate_stmt(scope, *stmt);
+            }
+            Statement::Default(stmt) => {
+                let label = self.switches.last().and_then(|switch| switch.default.clone())
+                    .expect("default label not within a switch statement");
+                self.code.add_label(label);
+                self.generate_stmt(scope, *stmt);
+            }
+            Statement::Labeled(label, stmt) => {
+                let label = self.user_label(&label);
+                self.code.add_label(label);
+                self.generate_stmt(scope, *stmt);
+            }
+            Statement::Goto(label) => {
+                let label = self.user_label(&label);
+                self.code.add_asm_line(&format!("jmp {}", label));
+            }
+            Statement::Break => {
+                let Some(labels) = self.loops.last()
+                else { panic!("break statement not within loop or switch") };
+                self.code.add_asm_line(&format!("jmp {}", labels.break_label));
+            }
+            Statement::Continue => {
+                let Some(label) = self.loops.iter().rev().find_map(|labels| labels.continue_label.as_ref())
+                else { panic!("continue statement not within a loop") };
+                self.code.add_asm_line(&format!("jmp {}", label));
+            }
+        }
+    }
+
+    /// The assembler label of a label in the C source. Identifiers can't
+    /// contain dots or start with a digit, so it can't clash with another
+    /// function's labels or those from `get_label`.
+    fn user_label(&self, label: &str) -> String {
+        format!(".L{}.{}", self.function_name, label)
+    }
+
+    /// Jumps from the value of the controlling expression in `%rax` to the
+    /// label of the matching case in `cases`, which are sorted, or else to
+    /// `default`. Dense cases use a jump table, others a binary search down
+    /// to a few compares.
+    fn generate_case_dispatch(&mut self, cases: &[(i64, String)], default: &str, signed: bool) {
+        if cases.len() < MIN_SEARCH_CASES {
+            for (value, label) in cases {
+                self.compare_rax(*value);
+                self.code.add_asm_line(&format!("je {}", label));
+            }
+            self.code.add_asm_line(&format!("jmp {}", default));
+            return;
+        }
+        let low = cases[0].0;
+        let range = cases[cases.len() - 1].0.wrapping_sub(low) as u64;
+        // At least a third of the table entries are cases
+        if range < 3 * cases.len() as u64 {
+            self.generate_jump_table(cases, range, default);
+            return;
+        }
+        let middle = cases.len() / 2;
+        let lower_half = self.get_label();
+        self.compare_rax(cases[middle].0);
+        self.code.add_asm_line(&format!("je {}", cases[middle].1));
+        self.code.add_asm_line(&format!("{} {}", if signed { "jl" } else { "jb" }, lower_half));
+        self.generate_case_dispatch(&cases[middle + 1..], default, signed);
+        self.code.add_label(lower_half);
+        self.generate_case_dispatch(&cases[..middle], default, signed);
+    }
+
+    /// Indexes a table of offsets by the value in `%rax` minus the lowest
+    /// case. Values below it wrap around and fail the bounds check too.
+    fn generate_jump_table(&mut self, cases: &[(i64, String)], range: u64, default: &str) {
+        let low = cases[0].0;
+        let table = self.get_label();
+        let targets = (0..=range)
+            .map(|index| {
+                let value = low.wrapping_add(index as i64);
+                cases.iter().find(|(case, _)| *case == value).map_or(default, |(_, label)| label).to_string()
+            })
+            .collect();
+        if i32::try_from(low).is_ok() {
+            self.code.add_asm_line(&format!("sub ${}, %rax", low));
+        } else {
+            self.code.add_asm_line(&format!("mov ${}, %rcx", low));
+            self.code.add_asm_line("sub %rcx, %rax");
+        }
+        self.code.add_asm_line(&format!("cmp ${}, %rax", range));
+        self.code.add_asm_line(&format!("ja {}", default));
+        self.code.add_asm_line(&format!("lea {}(%rip), %rcx", table));
+        self.code.add_asm_line("movslq (%rcx,%rax,4), %rax");
+        self.code.add_asm_line("add %rcx, %rax");
+        self.code.add_asm_line("jmp *%rax");
+        self.jump_tables.push((table, targets));
+    }
+
+    /// Compares `%rax` with `value`, which only fits in an immediate if it
+    /// is a sign-extended 32-bit number
+    fn compare_rax(&mut self, value: i64) {
+        if i32::try_from(value).is_ok() {
+            self.code.add_asm_line(&format!("cmp ${}, %rax", value));
+        } else {
+            self.code.add_asm_line(&format!("mov ${}, %rcx", value));
+            self.code.add_asm_line("cmp %rcx, %rax");
         }
     }
 
     fn generate_expr(&mut self, scope: &mut Scope, expr: Expression) {
-        match expr {
-            Expression::Int(x) => {
+        let expr_type = expr.expr_type().clone();
+        match expr.kind {
+            ExpressionKind::Int(x) => {
                 self.code.add_asm_line(&format!("mov ${}, %rax", x));
             }
-            Expression::UnaryOperation(op, expr) => {
-                self.generate_expr(scope, *expr);
+            ExpressionKind::String(s) => {
+                let label = self.get_label();
+                self.code.add_asm_line(&format!("lea {}(%rip), %rax", label));
+                self.strings.push((label, s));
+            }
+            ExpressionKind::Convert(operand) => {
+                let from = operand.expr_type().clone();
+                self.generate_expr(scope, *operand);
+                self.convert(&from, &expr_type);
+            }
+            ExpressionKind::UnaryOperation(UnaryOperator::AddressOf, operand) => {
+                self.generate_address(scope, *operand);
+            }
+            ExpressionKind::UnaryOperation(UnaryOperator::Dereference, operand) => {
+                self.generate_expr(scope, *operand);
+                self.load("(%rax)", &expr_type);
+            }
+            member @ ExpressionKind::Member(..) => {
+                self.generate_address(scope, Expression::typed(member, expr_type.clone()));
+                self.load("(%rax)", &expr_type);
+            }
+            ExpressionKind::UnaryOperation(op, operand) => {
+                self.generate_expr(scope, *operand);
                 match op {
-                    UnaryOperator::Negation => self.code.add_asm_line("neg %rax"),
-                    UnaryOperator::BitwiseNot => self.code.add_asm_line("not %rax"),
+                    UnaryOperator::Negation => {
+                        self.code.add_asm_line("neg %rax");
+                        self.normalize(&expr_type);
+                    }
+                    UnaryOperator::BitwiseNot => {
+                        self.code.add_asm_line("not %rax");
+                        self.normalize(&expr_type);
+                    }
                     UnaryOperator::LogicNot => {
                         self.code.add_asm_line("cmp $0, %rax");
                         self.code.add_asm_line("mov $0, %rax");
                         self.code.add_asm_line("sete %al");
                     }
+                    UnaryOperator::AddressOf | UnaryOperator::Dereference => unreachable!(),
                 }
             }
-            Expression::BinaryOperation(left, BinaryOperator::LogicOr, right) => {
+            ExpressionKind::BinaryOperation(left, BinaryOperator::LogicOr, right) => {
                 let clause2 = self.get_label();
                 let end = self.get_label();
                 self.generate_expr(scope, *left);
@@ -185,7 +1053,7 @@ impl CodeGenerator {
                 self.code.add_label(end);
             }
 
-            Expression::BinaryOperation(left, BinaryOperator::LogicAnd, right) => {
+            ExpressionKind::BinaryOperation(left, BinaryOperator::LogicAnd, right) => {
                 let clause2 = self.get_label();
                 let end = self.get_label();
                 self.generate_expr(scope, *left);
@@ -201,71 +1069,120 @@ impl CodeGenerator {
                 self.code.add_label(end);
             }
 
-            Expression::BinaryOperation(left, BinaryOperator::Assign, right) => {
-                if let Expression::Variable(name) = *left {
-                    self.generate_expr(scope, *right);
-                    self.code.add_asm_line(
-                        &format!(
-                            "movl %eax, {}(%rbp)",
-                            scope.get_symbol(&name)
-                        )
-                    );
-                } else {
-                    panic!("Invalid assignment");
-                }
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                let else_label = self.get_label();
+                let end = self.get_label();
+                self.generate_expr(scope, *condition);
+                self.code.add_asm_line("cmp $0, %rax");
+                self.code.add_asm_line(&format!("je {}", else_label));
+                self.generate_expr(scope, *then);
+                self.code.add_asm_line(&format!("jmp {}", end));
+                self.code.add_label(else_label);
+                self.generate_expr(scope, *otherwise);
+                self.code.add_label(end);
+            }
+
+            ExpressionKind::BinaryOperation(left, BinaryOperator::Assign, right) => {
+                self.generate_address(scope, *left);
+                self.push("%rax");
+                self.generate_expr(scope, *right);
+                self.pop("%rcx");
+                self.store("(%rcx)", &expr_type);
+            }
+
+            // Constant shift counts can be immediates instead of going
+            // through %cl
+            ExpressionKind::BinaryOperation(left, op @ (BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight), right)
+                if evaluate_constant(&right).is_ok() =>
+            {
+                let count = evaluate_constant(&right).unwrap() & 63;
+                let instruction = match op {
+                    BinaryOperator::ShiftLeft => "shl",
+                    _ if left.expr_type().is_signed() => "sar",
+                    _ => "shr",
+                };
+                self.generate_expr(scope, *left);
+                self.code.add_asm_line(&format!("{} ${}, %rax", instruction, count));
+                self.normalize(&expr_type);
             }
 
-            Expression::BinaryOperation(left, op, right) => {
+            ExpressionKind::BinaryOperation(left, BinaryOperator::Remainder, right)
+                if evaluate_constant(&right).is_ok_and(|divisor| can_lower_remainder(divisor, &expr_type)) =>
+            {
+                let divisor = evaluate_constant(&right).unwrap();
+                self.generate_expr(scope, *left);
+                self.generate_constant_remainder(divisor, &expr_type);
+            }
+
+            ExpressionKind::BinaryOperation(left, op, right) => {
+                let left_type = left.expr_type().clone();
+                let right_type = right.expr_type().clone();
                 self.generate_expr(scope, *right);
-                self.code.add_asm_line("push %rax");
+                self.push("%rax");
                 self.generate_expr(scope, *left);
-                self.code.add_asm_line("pop %rcx");
-                match op {
-                    BinaryOperator::Plus => self.code.add_asm_line("add %rcx, %rax"),
-                    BinaryOperator::Minus => self.code.add_asm_line("sub %rcx, %rax"),
-                    BinaryOperator::Times => self.code.add_asm_line("imul %rcx, %rax"),
-                    BinaryOperator::Divide => {
-                        self.code.add_asm_line("cqo");
-                        self.code.add_asm_line("idiv %rcx");
-                    }
-                    BinaryOperator::EQ => {
-                        self.code.add_asm_line("cmp %rcx, %rax");
-                        self.code.add_asm_line("mov $0, %rax");
-                        self.code.add_asm_line("sete %al");
-                    }
-                    BinaryOperator::NEQ => {
-                        self.code.add_asm_line("cmp %rcx, %rax");
-                        self.code.add_asm_line("mov $0, %rax");
-                        self.code.add_asm_line("setne %al");
-                    }
-                    BinaryOperator::LT => {
-                        self.code.add_asm_line("cmp %rcx, %rax");
-                        self.code.add_asm_line("mov $0, %rax");
-                        self.code.add_asm_line("setl %al");
-                    }
-                    BinaryOperator::GT => {
-                        self.code.add_asm_line("cmp %rcx, %rax");
-                        self.code.add_asm_line("mov $0, %rax");
-                        self.code.add_asm_line("setg %al");
-                    }
-                    BinaryOperator::LE => {
-                        self.code.add_asm_line("cmp %rcx, %rax");
-                        self.code.add_asm_line("mov $0, %rax");
-                        self.code.add_asm_line("setle %al");
-                    }
-                    BinaryOperator::GE => {
-                        self.code.add_asm_line("cmp %rcx, %rax");
-                        self.code.add_asm_line("mov $0, %rax");
-                        self.code.add_asm_line("setge %al");
-                    }
-                    _ => unreachable!(),
+                self.pop("%rcx");
+                self.generate_binary_operator(op, &left_type, &right_type, &expr_type);
+            }
+
+            ExpressionKind::Prefix(op, operand) => self.generate_increment(scope, op, *operand, false),
+            ExpressionKind::Postfix(op, operand) => self.generate_increment(scope, op, *operand, true),
+
+            ExpressionKind::CompoundAssignment(op, target, value) => {
+                let value_type = value.expr_type().clone();
+                let operation_type = if expr_type.is_pointer()
+                    || matches!(op, BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight)
+                {
+                    expr_type.promote()
+                } else {
+                    value_type.clone()
+                };
+                // The address is only worked out once, and stays on the stack
+                // until the result is stored
+                self.generate_address(scope, *target);
+                self.push("%rax");
+                self.generate_expr(scope, *value);
+                self.code.add_asm_line("mov %rax, %rcx");
+                self.code.add_asm_line("mov (%rsp), %rax");
+                self.load("(%rax)", &expr_type);
+                self.convert(&expr_type, &operation_type);
+                self.generate_binary_operator(op, &operation_type, &value_type, &operation_type);
+                self.convert(&operation_type, &expr_type);
+                self.pop("%rcx");
+                self.store("(%rcx)", &expr_type);
+            }
+
+            ExpressionKind::FunctionCall(name, arguments) => {
+                let stack_arguments = arguments.len().saturating_sub(ARGUMENT_REGISTERS.len()) as i64;
+                let padding = (self.stack_depth + 8 * stack_arguments) % 16;
+                if padding != 0 {
+                    self.code.add_asm_line(&format!("sub ${}, %rsp", padding));
+                    self.stack_depth += padding;
+                }
+                // Pushing from the last argument leaves the first ones on
+                // top, ready to be popped into their registers
+                let register_arguments = arguments.len() - stack_arguments as usize;
+                for arg in arguments.into_iter().rev() {
+                    self.generate_expr(scope, arg);
+                    self.push("%rax");
+                }
+                for register in &ARGUMENT_REGISTERS[..register_arguments] {
+                    self.pop(register);
+                }
+                // Variadic callees expect the number of vector registers in %al
+                self.code.add_asm_line("mov $0, %eax");
+                self.code.add_asm_line(&format!("call {}", name));
+                let cleanup = 8 * stack_arguments + padding;
+                if cleanup > 0 {
+                    self.code.add_asm_line(&format!("add ${}, %rsp", cleanup));
+                    self.stack_depth -= cleanup;
                 }
+                // Only the low bits of the returned register are defined
+                self.normalize(&expr_type);
             }
 
-            Expression::Variable(name) => {
-                let offset = scope.get_symbol(&name);
-                self.code.add_asm_line("xor %rax, %rax");
-                self.code.add_asm_line(&format!("movl {}(%rbp), %eax", offset));
+            ExpressionKind::Variable(name) => {
+                let operand = scope.get_symbol(&name).operand();
+                self.load(&operand, &expr_type);
             }
         }
     }
@@ -1,33 +1,53 @@
 use std::str::Chars;
 use std::iter::Peekable;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Keyword(String),
     Identifier(String),
     Constant(String),
+    /// Contents of a string literal, with escape sequences already replaced
+    StringLiteral(Vec<u8>),
+    CharLiteral(u8),
     LeftParen, RightParen,
     LeftBrace, RightBrace,
-    Semicolon,
+    LeftBracket, RightBracket,
+    Semicolon, Comma,
     Plus, Minus,
-    Times, Divide,
-    BitwiseNot,
+    Increment, Decrement,
+    Times, Divide, Remainder,
+    BitwiseNot, BitwiseAnd,
+    BitwiseOr, BitwiseXor,
+    ShiftLeft, ShiftRight,
     LogicNot,
     LogicAnd, LogicOr,
     EQ, NEQ,
     LT, LE,
     GT, GE,
     Assign,
+    PlusAssign, MinusAssign,
+    TimesAssign, DivideAssign, RemainderAssign,
+    ShiftLeftAssign, ShiftRightAssign,
+    BitwiseAndAssign, BitwiseXorAssign, BitwiseOrAssign,
+    Question, Colon,
+    Dot, Arrow,
 }
 
 const KEYWORDS: &[&str] = &[
-    "int", "return",
+    "char", "short", "int", "long", "signed", "unsigned", "void",
+    "struct", "union", "enum",
+    "static", "extern", "typedef",
+    "return", "if", "else",
+    "while", "do", "for", "break", "continue",
+    "switch", "case", "default", "goto",
 ];
 
+/// Reads a numeric constant, including any suffix such as `L`
 fn get_number(chars: &mut Peekable<Chars>) -> String {
     let mut number = String::new();
     while let Some(c) = chars.peek() {
-        if !c.is_numeric() {
+        if !c.is_alphanumeric() {
             break;
         }
         number.push(*c);
@@ -39,7 +59,7 @@ fn get_number(chars: &mut Peekable<Chars>) -> String {
 fn get_word(chars: &mut Peekable<Chars>) -> String {
     let mut word = String::new();
     while let Some(c) = chars.peek() {
-        if !c.is_alphanumeric() {
+        if !c.is_alphanumeric() && *c != '_' {
             break;
         }
         word.push(*c);
@@ -48,12 +68,84 @@ fn get_word(chars: &mut Peekable<Chars>) -> String {
     word
 }
 
+/// Reads the escape sequence after a backslash, returning the byte it stands
+/// for.
+fn get_escape(chars: &mut Peekable<Chars>) -> u8 {
+    let c = chars.next().expect("Unterminated escape sequence");
+    match c {
+        'n' => b'\n',
+        't' => b'\t',
+        'r' => b'\r',
+        'a' => 0x07,
+        'b' => 0x08,
+        'f' => 0x0c,
+        'v' => 0x0b,
+        '\\' | '\'' | '"' | '?' => c as u8,
+        '0'..='7' => {
+            // Up to three octal digits
+            let mut value = c.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(digit) => {
+                        value = value * 8 + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            u8::try_from(value).unwrap_or_else(|_| panic!("Octal escape sequence out of range"))
+        }
+        'x' => {
+            let mut value: u32 = 0;
+            let mut digits = 0;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                value = value.saturating_mul(16).saturating_add(digit);
+                digits += 1;
+                chars.next();
+            }
+            if digits == 0 {
+                panic!("\\x used with no following hex digits");
+            }
+            u8::try_from(value).unwrap_or_else(|_| panic!("Hex escape sequence out of range"))
+        }
+        _ => panic!("Unknown escape sequence: '\\{}'", c),
+    }
+}
+
+/// Reads the characters of a string or character literal up to the closing
+/// `delimiter`, which has to be on the same line.
+fn get_quoted(chars: &mut Peekable<Chars>, delimiter: char) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    chars.next();
+    loop {
+        match chars.next() {
+            Some(c) if c == delimiter => break,
+            Some('\\') => bytes.push(get_escape(chars)),
+            Some('\n') | None => panic!("Missing terminating {} character", delimiter),
+            Some(c) => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
+    }
+    bytes
+}
+
 pub fn lex(input: &str) -> Vec<Token> {
     let mut tokens = Vec::new();
     let mut chars = input.chars().peekable();
     while let Some(c) = chars.peek() {
         match c {
             '0'..='9' => tokens.push(Token::Constant(get_number(&mut chars))),
+            '"' => tokens.push(Token::StringLiteral(get_quoted(&mut chars, '"'))),
+            '\'' => {
+                let bytes = get_quoted(&mut chars, '\'');
+                match bytes[..] {
+                    [byte] => tokens.push(Token::CharLiteral(byte)),
+                    [] => panic!("Empty character constant"),
+                    _ => panic!("Multi-character constants are not supported"),
+                }
+            }
             '(' => {
                 tokens.push(Token::LeftParen);
                 chars.next();
@@ -70,24 +162,104 @@ pub fn lex(input: &str) -> Vec<Token> {
                 tokens.push(Token::RightBrace);
                 chars.next();
             }
+            '[' => {
+                tokens.push(Token::LeftBracket);
+                chars.next();
+            }
+            ']' => {
+                tokens.push(Token::RightBracket);
+                chars.next();
+            }
             ';' => {
                 tokens.push(Token::Semicolon);
                 chars.next();
             }
+            ',' => {
+                tokens.push(Token::Comma);
+                chars.next();
+            }
             '+' => {
-                tokens.push(Token::Plus);
                 chars.next();
+                match chars.peek() {
+                    Some('=') => {
+                        tokens.push(Token::PlusAssign);
+                        chars.next();
+                    }
+                    Some('+') => {
+                        tokens.push(Token::Increment);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::Plus),
+                }
             }
             '*' => {
-                tokens.push(Token::Times);
                 chars.next();
+                match chars.peek() {
+                    Some('=') => {
+                        tokens.push(Token::TimesAssign);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::Times),
+                }
             }
             '/' => {
-                tokens.push(Token::Divide);
                 chars.next();
+                match chars.peek() {
+                    Some('=') => {
+                        tokens.push(Token::DivideAssign);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::Divide),
+                }
+            }
+            '%' => {
+                chars.next();
+                match chars.peek() {
+                    Some('=') => {
+                        tokens.push(Token::RemainderAssign);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::Remainder),
+                }
+            }
+            '^' => {
+                chars.next();
+                match chars.peek() {
+                    Some('=') => {
+                        tokens.push(Token::BitwiseXorAssign);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::BitwiseXor),
+                }
             }
             '-' => {
-                tokens.push(Token::Minus);
+                chars.next();
+                match chars.peek() {
+                    Some('>') => {
+                        tokens.push(Token::Arrow);
+                        chars.next();
+                    }
+                    Some('=') => {
+                        tokens.push(Token::MinusAssign);
+                        chars.next();
+                    }
+                    Some('-') => {
+                        tokens.push(Token::Decrement);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::Minus),
+                }
+            }
+            '.' => {
+                tokens.push(Token::Dot);
+                chars.next();
+            }
+            '?' => {
+                tokens.push(Token::Question);
+                chars.next();
+            }
+            ':' => {
+                tokens.push(Token::Colon);
                 chars.next();
             }
             '~' => {
@@ -113,6 +285,16 @@ pub fn lex(input: &str) -> Vec<Token> {
                         tokens.push(Token::LE);
                         chars.next();
                     }
+                    Some('<') => {
+                        chars.next();
+                        match chars.peek() {
+                            Some('=') => {
+                                tokens.push(Token::ShiftLeftAssign);
+                                chars.next();
+                            }
+                            _ => tokens.push(Token::ShiftLeft),
+                        }
+                    }
                     _ => tokens.push(Token::LT),
                 }
             }
@@ -124,6 +306,16 @@ pub fn lex(input: &str) -> Vec<Token> {
                         tokens.push(Token::GE);
                         chars.next();
                     }
+                    Some('>') => {
+                        chars.next();
+                        match chars.peek() {
+                            Some('=') => {
+                                tokens.push(Token::ShiftRightAssign);
+                                chars.next();
+                            }
+                            _ => tokens.push(Token::ShiftRight),
+                        }
+                    }
                     _ => tokens.push(Token::GT),
                 }
             }
@@ -146,7 +338,11 @@ pub fn lex(input: &str) -> Vec<Token> {
                         tokens.push(Token::LogicOr);
                         chars.next();
                     }
-                    _ => panic!("Bitwise or not implemented"),
+                    Some('=') => {
+                        tokens.push(Token::BitwiseOrAssign);
+                        chars.next();
+                    }
+                    _ => tokens.push(Token::BitwiseOr),
                 }
             }
             '&' => {
@@ -157,10 +353,15 @@ pub fn lex(input: &str) -> Vec<Token> {
                         tokens.push(Token::LogicAnd);
                         chars.next();
                     }
-                    _ => panic!("Bitwise and not implemented"),
+                    Some('=') => {
+                        tokens.push(Token::BitwiseAndAssign);
+                        chars.next();
+                    }
+                    // Either bitwise and or address-of, depending on context
+                    _ => tokens.push(Token::BitwiseAnd),
                 }
             }
-            'a'..='z' | 'A'..='Z' => {
+            'a'..='z' | 'A'..='Z' | '_' => {
                 let word = get_word(&mut chars);
                 if KEYWORDS.contains(&word.as_str()) {
                     tokens.push(Token::Keyword(word));
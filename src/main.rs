@@ -1,6 +1,8 @@
 mod lexer;
 mod parser;
+mod sema;
 mod codegen;
+mod types;
 
 use std::env;
 use std::fs;
@@ -29,7 +31,20 @@ fn main() {
         return;
     }
 
+    let ast = match sema::analyze(ast) {
+        Ok((ast, warnings)) => {
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            ast
+        }
+        Err(message) => {
+            eprintln!("error: {}", message);
+            std::process::exit(1);
+        }
+    };
+
     let mut codegenerator = codegen::CodeGenerator::new();
     codegenerator.generate(ast);
-    println!("{}", codegenerator.code.to_string());
+    println!("{}", codegenerator.code);
 }
@@ -1,24 +1,96 @@
 use crate::lexer::Token;
-use std::collections::VecDeque;
+use crate::types::{StructType, Type};
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub struct Program {
-    pub declarations: Vec<FunctionDeclaration>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Debug)]
+pub enum Declaration {
+    Function(FunctionDeclaration),
+    Variable(VariableDeclaration),
+    /// A declaration of a type alone, such as `struct s { ... };`
+    Type(Type),
+    /// `typedef type name;`. Uses of the name have already been replaced by
+    /// the type.
+    Typedef(String, Type),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageClass {
+    Static,
+    Extern,
+}
+
+/// Whether a name declared at file scope or `extern` refers to the same
+/// object or function in other translation units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linkage {
+    External,
+    Internal,
+}
+
+/// A variable declared either at file scope or inside a block
+#[derive(Debug)]
+pub struct VariableDeclaration {
+    pub var_type: Type,
+    pub name: String,
+    pub initializer: Option<Expression>,
+    pub storage_class: Option<StorageClass>,
+    /// Worked out by semantic analysis for variables declared at file scope
+    /// or `extern`
+    pub linkage: Option<Linkage>,
 }
 
 #[derive(Debug)]
 pub struct FunctionDeclaration {
-    pub return_type: String,
+    pub return_type: Type,
     pub name: String,
-    // pub parameters: Vec<String>,
-    pub body: Vec<Statement>,
+    pub parameters: Vec<Parameter>,
+    /// False for `f()`, which says nothing about the parameters, as opposed
+    /// to `f(void)`
+    pub has_prototype: bool,
+    /// `None` for prototypes
+    pub body: Option<Vec<Statement>>,
+    pub storage_class: Option<StorageClass>,
+    /// Worked out by semantic analysis
+    pub linkage: Option<Linkage>,
+}
+
+#[derive(Debug)]
+pub struct Parameter {
+    pub param_type: Type,
+    /// May be omitted in prototypes
+    pub name: Option<String>,
 }
 
 #[derive(Debug)]
 pub enum Statement {
     Return(Expression),
-    Declare(String, Option<Expression>),
+    Block(Vec<Statement>),
+    Declare(VariableDeclaration),
+    DeclareType(Type),
+    Typedef(String, Type),
     Expression(Expression),
+    If(Expression, Box<Statement>, Option<Box<Statement>>),
+    While(Expression, Box<Statement>),
+    DoWhile(Box<Statement>, Expression),
+    /// `for (init; condition; step) body`. `init` is either a declaration or
+    /// an expression statement.
+    For(Option<Box<Statement>>, Option<Expression>, Option<Expression>, Box<Statement>),
+    Switch(Expression, Box<Statement>),
+    /// A statement with a `case` label. Semantic analysis replaces the
+    /// label's expression by its value.
+    Case(Expression, Box<Statement>),
+    Default(Box<Statement>),
+    /// `label: statement`
+    Labeled(String, Box<Statement>),
+    Goto(String),
+    Break,
+    Continue,
 }
 
 #[derive(Debug)]
@@ -26,123 +98,253 @@ pub enum UnaryOperator {
     Negation,
     BitwiseNot,
     LogicNot,
+    AddressOf,
+    Dereference,
 }
 
-#[derive(Debug)]
+/// `++` or `--`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IncrementOperator {
+    Increment,
+    Decrement,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BinaryOperator {
     Plus, Minus,
-    Times, Divide,
+    Times, Divide, Remainder,
+    ShiftLeft, ShiftRight,
+    BitwiseAnd, BitwiseXor, BitwiseOr,
     LogicAnd, LogicOr,
     EQ, NEQ, LT, GT, LE, GE,
     Assign,
 }
 
 #[derive(Debug)]
-pub enum Expression {
-    Int(i32),
+pub struct Expression {
+    pub kind: ExpressionKind,
+    /// Filled in by semantic analysis, except for constants whose type is
+    /// known as soon as they are parsed
+    pub expr_type: Option<Type>,
+}
+
+#[derive(Debug)]
+pub enum ExpressionKind {
+    Int(i64),
+    /// A string literal, without the terminating null byte
+    String(Vec<u8>),
     Variable(String),
     UnaryOperation(UnaryOperator, Box<Expression>),
     BinaryOperation(Box<Expression>, BinaryOperator, Box<Expression>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    FunctionCall(String, Vec<Expression>),
+    /// `s.member`. `p->member` is parsed as `(*p).member`.
+    Member(Box<Expression>, String),
+    /// `target op= value`. Semantic analysis converts the value to the type
+    /// the operation is done in, which is the common type of both operands
+    /// except when shifting or offsetting a pointer.
+    CompoundAssignment(BinaryOperator, Box<Expression>, Box<Expression>),
+    /// `++x` or `--x`, whose value is the updated one
+    Prefix(IncrementOperator, Box<Expression>),
+    /// `x++` or `x--`, whose value is the one from before the update
+    Postfix(IncrementOperator, Box<Expression>),
+    /// An implicit conversion of the operand to the type of this expression,
+    /// inserted by semantic analysis
+    Convert(Box<Expression>),
 }
 
-pub fn parse(tokens: Vec<Token>) -> Program {
-    Program {
-        declarations: vec![parse_function_declaration(tokens)],
+impl Expression {
+    pub fn new(kind: ExpressionKind) -> Self {
+        Self { kind, expr_type: None }
     }
-}
 
-fn expect_token(tokens: &mut VecDeque<Token>, expected: Token) {
-    assert_eq!(
-        tokens.pop_front().expect("Expected token {expected:?}"),
-        expected
-    );
-}
-
-fn parse_function_declaration(tks: Vec<Token>) -> FunctionDeclaration {
-    let mut tokens = VecDeque::from(tks);
-    let return_type_tok = tokens.pop_front().expect("Expected function return type");
-    let return_type: String;
-    match return_type_tok {
-        Token::Keyword(s) => {
-            assert_eq!(s, "int");
-            return_type = s;
-        },
-        _ => panic!("Unexpected return type {return_type_tok:?}"),
-    }
-    let name_tok = tokens.pop_front().expect("Expected function name");
-    let name: String;
-    match name_tok {
-        Token::Identifier(s) => {
-            name = s;
-        },
-        _ => panic!("Unexpected token {name_tok:?}. Function name expected"),
+    pub fn typed(kind: ExpressionKind, expr_type: Type) -> Self {
+        Self { kind, expr_type: Some(expr_type) }
     }
-    expect_token(&mut tokens, Token::LeftParen);
-    expect_token(&mut tokens, Token::RightParen);
-    expect_token(&mut tokens, Token::LeftBrace);
-    let body = parse_statements(&mut tokens);
-    expect_token(&mut tokens, Token::RightBrace);
-    FunctionDeclaration {
-        return_type,
-        name,
-        body,
+
+    /// The type of an expression that went through semantic analysis
+    pub fn expr_type(&self) -> &Type {
+        self.expr_type.as_ref().expect("Expression has not been type checked")
     }
 }
 
-fn parse_statements(tokens: &mut VecDeque<Token>) -> Vec<Statement> {
-    let mut statements = Vec::new();
-    while let Some(token) = tokens.front() {
-        // TODO: Handle nested blocks
-        if *token == Token::RightBrace {
-            break;
-        }
-        statements.push(parse_statement(tokens));
+pub fn parse(tks: Vec<Token>) -> Program {
+    let mut parser = Parser {
+        tokens: VecDeque::from(tks),
+        scopes: vec![ParseScope::default()],
+    };
+    let mut declarations = Vec::new();
+    while !parser.tokens.is_empty() {
+        declarations.push(parser.parse_top_level_declaration());
     }
-    statements
+    Program { declarations }
 }
 
-fn parse_statement(tokens: &mut VecDeque<Token>) -> Statement {
-    // println!("Function parse_statement called");
-    // println!("Tokens: {:?}", tokens);
-    let token = tokens.front().expect("Expected statement");
-    match token {
-        Token::Keyword(s) => match s.as_str() {
-            "return" => {
-                tokens.pop_front();
-                let expr = parse_expression(tokens);
-                expect_token(tokens, Token::Semicolon);
-                Statement::Return(expr)
-            }
-            "int" => {
-                tokens.pop_front();
-                let Token::Identifier(name) = tokens.pop_front().expect("Expected variable name")
-                else { panic!("Unexpected token, identifier expected") };
-                if let Some(Token::Assign) = tokens.front() {
-                    tokens.pop_front();
-                    let expr = parse_expression(tokens);
-                    expect_token(tokens, Token::Semicolon);
-                    Statement::Declare(name, Some(expr))
-                } else {
-                    expect_token(tokens, Token::Semicolon);
-                    Statement::Declare(name, None)
+/// Evaluates an expression that must be known at compile time, such as the
+/// initializer of a global variable. Once the expression has been type
+/// checked, the result wraps around and compares like its type does.
+pub fn evaluate_constant(expr: &Expression) -> Result<i64, String> {
+    let value = match &expr.kind {
+        ExpressionKind::Int(x) => *x,
+        ExpressionKind::UnaryOperation(op, operand) => {
+            let value = evaluate_constant(operand)?;
+            match op {
+                UnaryOperator::Negation => value.wrapping_neg(),
+                UnaryOperator::BitwiseNot => !value,
+                UnaryOperator::LogicNot => (value == 0) as i64,
+                UnaryOperator::AddressOf | UnaryOperator::Dereference => {
+                    return Err("Initializer element is not constant".to_string())
+                }
+            }
+        }
+        ExpressionKind::BinaryOperation(left, op, right) => {
+            let unsigned = left.expr_type.as_ref().is_some_and(|t| t.is_integer() && !t.is_signed());
+            let left = evaluate_constant(left)?;
+            let right = evaluate_constant(right)?;
+            match op {
+                BinaryOperator::Plus => left.wrapping_add(right),
+                BinaryOperator::Minus => left.wrapping_sub(right),
+                BinaryOperator::Times => left.wrapping_mul(right),
+                BinaryOperator::Divide => {
+                    if right == 0 {
+                        return Err("Division by zero in constant expression".to_string());
+                    }
+                    if unsigned {
+                        ((left as u64) / (right as u64)) as i64
+                    } else {
+                        left.wrapping_div(right)
+                    }
+                }
+                BinaryOperator::Remainder => {
+                    if right == 0 {
+                        return Err("Division by zero in constant expression".to_string());
+                    }
+                    if unsigned {
+                        ((left as u64) % (right as u64)) as i64
+                    } else {
+                        left.wrapping_rem(right)
+                    }
                 }
+                BinaryOperator::ShiftLeft => left.wrapping_shl(right as u32),
+                BinaryOperator::ShiftRight if unsigned => (left as u64).wrapping_shr(right as u32) as i64,
+                BinaryOperator::ShiftRight => left.wrapping_shr(right as u32),
+                BinaryOperator::BitwiseAnd => left & right,
+                BinaryOperator::BitwiseXor => left ^ right,
+                BinaryOperator::BitwiseOr => left | right,
+                BinaryOperator::LogicAnd => (left != 0 && right != 0) as i64,
+                BinaryOperator::LogicOr => (left != 0 || right != 0) as i64,
+                BinaryOperator::EQ => (left == right) as i64,
+                BinaryOperator::NEQ => (left != right) as i64,
+                BinaryOperator::LT if unsigned => ((left as u64) < (right as u64)) as i64,
+                BinaryOperator::GT if unsigned => ((left as u64) > (right as u64)) as i64,
+                BinaryOperator::LE if unsigned => ((left as u64) <= (right as u64)) as i64,
+                BinaryOperator::GE if unsigned => ((left as u64) >= (right as u64)) as i64,
+                BinaryOperator::LT => (left < right) as i64,
+                BinaryOperator::GT => (left > right) as i64,
+                BinaryOperator::LE => (left <= right) as i64,
+                BinaryOperator::GE => (left >= right) as i64,
+                BinaryOperator::Assign => return Err("Assignment in constant expression".to_string()),
             }
-            _ => panic!("Keyword {s} not supported"),
         }
-        _ => {
-            let expr = parse_expression(tokens);
-            expect_token(tokens, Token::Semicolon);
-            Statement::Expression(expr)
+        ExpressionKind::Conditional(condition, then, otherwise) => {
+            if evaluate_constant(condition)? != 0 {
+                evaluate_constant(then)?
+            } else {
+                evaluate_constant(otherwise)?
+            }
+        }
+        ExpressionKind::Convert(operand) if operand.expr_type().is_integer() => evaluate_constant(operand)?,
+        _ => return Err("Initializer element is not constant".to_string()),
+    };
+    Ok(match &expr.expr_type {
+        Some(t) if t.is_integer() => t.wrap(value),
+        _ => value,
+    })
+}
+
+/// Whether two typedefs of the same name in the same scope declare the same
+/// type. Struct tags aren't resolved yet, but a tag can only name one struct
+/// in a scope, so two mentions of it that don't both define it agree.
+fn same_typedef_target(a: &Type, b: &Type) -> bool {
+    match (a, b) {
+        (Type::Pointer(a), Type::Pointer(b)) => same_typedef_target(a, b),
+        (Type::Array(a, a_length), Type::Array(b, b_length)) => a_length == b_length && same_typedef_target(a, b),
+        (Type::Struct(a), Type::Struct(b)) => {
+            a == b
+                || (a.tag.is_some() && a.tag == b.tag && a.is_union == b.is_union
+                    && (a.definition.is_none() || b.definition.is_none()))
         }
+        _ => a == b,
     }
 }
 
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Keyword(s)) if s == keyword)
+}
+
+/// Parses a decimal, octal or hexadecimal constant with an optional `u`,
+/// `l` or `ll` suffix. Its type is the first one able to hold the value
+/// among those the suffix allows (C11 6.4.4.1).
+fn parse_integer_constant(s: &str) -> Expression {
+    let (radix, digits) = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        (16, hex)
+    } else if s.starts_with('0') && s[1..].starts_with(|c: char| c.is_ascii_digit()) {
+        (8, &s[1..])
+    } else {
+        (10, s)
+    };
+    let digits_end = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(digits_end);
+    let value = u64::from_str_radix(digits, radix)
+        .unwrap_or_else(|_| panic!("Invalid integer constant {s}"));
+    let unsigned = suffix.contains(['u', 'U']);
+    let long = match suffix.replace(['u', 'U'], "").as_str() {
+        "" => 0,
+        "l" | "L" => 1,
+        "ll" | "LL" => 2,
+        _ => panic!("Invalid suffix \"{suffix}\" on integer constant"),
+    };
+    let candidates = [Type::Int, Type::Long, Type::LongLong];
+    let constant_type = candidates[long..].iter()
+        .flat_map(|t| {
+            if unsigned {
+                vec![t.to_unsigned()]
+            } else if radix == 10 {
+                vec![t.clone()]
+            } else {
+                // Octal and hexadecimal constants may also be unsigned
+                vec![t.clone(), t.to_unsigned()]
+            }
+        })
+        .find(|t| {
+            let bits = 8 * t.size() - if t.is_signed() { 1 } else { 0 };
+            u128::from(value) < 1 << bits
+        })
+        .unwrap_or_else(|| panic!("Integer constant {s} is too large"));
+    Expression::typed(ExpressionKind::Int(value as i64), constant_type)
+}
+
+fn is_type_specifier(token: Option<&Token>) -> bool {
+    matches!(
+        token,
+        Some(Token::Keyword(s))
+            if matches!(s.as_str(), "char" | "short" | "int" | "long" | "signed" | "unsigned" | "struct" | "union" | "enum")
+    )
+}
+
 fn token_to_binary_operator(token: Token) -> BinaryOperator {
     match token {
         Token::Plus => BinaryOperator::Plus,
         Token::Minus => BinaryOperator::Minus,
         Token::Times => BinaryOperator::Times,
         Token::Divide => BinaryOperator::Divide,
+        Token::Remainder => BinaryOperator::Remainder,
+        Token::ShiftLeft => BinaryOperator::ShiftLeft,
+        Token::ShiftRight => BinaryOperator::ShiftRight,
+        Token::BitwiseAnd => BinaryOperator::BitwiseAnd,
+        Token::BitwiseXor => BinaryOperator::BitwiseXor,
+        Token::BitwiseOr => BinaryOperator::BitwiseOr,
         Token::LogicAnd => BinaryOperator::LogicAnd,
         Token::LogicOr => BinaryOperator::LogicOr,
         Token::EQ => BinaryOperator::EQ,
@@ -158,72 +360,804 @@ fn token_to_binary_operator(token: Token) -> BinaryOperator {
 
 macro_rules! parse_binary_operator {
     ($func_name:ident, $next_parse:ident, $pattern:pat) => {
-    fn $func_name(tokens: &mut VecDeque<Token>) -> Expression {
+    fn $func_name(&mut self) -> Expression {
         // println!("Function {} called", stringify!($func_name));
-        // println!("Tokens: {:?}", tokens);
-        let mut left = $next_parse(tokens);
-        while matches!(tokens.front(), Some($pattern)) {
-            let token = tokens.pop_front().unwrap();
-            let right = $next_parse(tokens);
-            left = Expression::BinaryOperation(
+        // println!("Tokens: {:?}", self.tokens);
+        let mut left = self.$next_parse();
+        while matches!(self.tokens.front(), Some($pattern)) {
+            let token = self.tokens.pop_front().unwrap();
+            let right = self.$next_parse();
+            left = Expression::new(ExpressionKind::BinaryOperation(
                 Box::new(left),
                 token_to_binary_operator(token),
                 Box::new(right),
-            );
+            ));
         }
         left
     }
     };
 }
 
-fn parse_expression(tokens: &mut VecDeque<Token>) -> Expression {
-    let left = parse_logic_or_expression(tokens);
-    match tokens.front() {
-        Some(Token::Assign) => {
-            tokens.pop_front();
-            let right = parse_expression(tokens);
-            Expression::BinaryOperation(
-                Box::new(left),
-                BinaryOperator::Assign,
-                Box::new(right),
-            )
+/// What the parser needs to know about an ordinary identifier
+enum Binding {
+    /// A variable or function
+    Object,
+    /// An enumeration constant and its value
+    Enumerator(i64),
+    /// A typedef name and the type it stands for
+    Typedef(Type),
+}
+
+/// What a tag declared in a block names. Structs and unions are resolved
+/// by semantic analysis, but struct, union and enum tags share one
+/// namespace, so the parser tracks which kind each tag is.
+enum Tag {
+    StructOrUnion,
+    /// An enum and the type it stands for
+    Enum(Type),
+}
+
+/// The identifiers declared in a block, which the parser tracks so that
+/// enumeration constants can be folded wherever they are used and typedef
+/// names can be told apart from variables
+#[derive(Default)]
+struct ParseScope {
+    identifiers: HashMap<String, Binding>,
+    tags: HashMap<String, Tag>,
+}
+
+struct Parser {
+    tokens: VecDeque<Token>,
+    /// Innermost scope last
+    scopes: Vec<ParseScope>,
+}
+
+impl Parser {
+    /// Records a declaration of `name` in the current scope. Enumeration
+    /// constants can't share a scope with any other declaration of the same
+    /// name, and a typedef name can only be redeclared as the same type.
+    fn declare(&mut self, name: &str, binding: Binding) {
+        let identifiers = &mut self.scopes.last_mut().unwrap().identifiers;
+        match (identifiers.get(name), &binding) {
+            (Some(Binding::Object), Binding::Object) => {}
+            (Some(Binding::Typedef(previous)), Binding::Typedef(target)) => {
+                if !same_typedef_target(previous, target) {
+                    panic!("Conflicting types for typedef {name}");
+                }
+            }
+            (Some(_), _) => panic!("Redeclaration of {name}"),
+            (None, _) => {}
         }
-        _ => left,
+        identifiers.insert(name.to_string(), binding);
     }
-}
 
-parse_binary_operator!(parse_logic_or_expression, parse_logic_and_expr, Token::LogicOr);
-parse_binary_operator!(parse_logic_and_expr, parse_eq_expr, Token::LogicAnd);
-parse_binary_operator!(parse_eq_expr, parse_rel_expr, Token::EQ | Token::NEQ);
-parse_binary_operator!(parse_rel_expr, parse_add_expr, Token::LT | Token::GT | Token::LE | Token::GE);
-parse_binary_operator!(parse_add_expr, parse_term, Token::Plus | Token::Minus);
-parse_binary_operator!(parse_term, parse_factor, Token::Times | Token::Divide);
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.identifiers.get(name))
+    }
 
-fn parse_factor(tokens: &mut VecDeque<Token>) -> Expression {
-    let token = tokens.pop_front().expect("Expected a factor");
-    match token {
-        Token::Constant(s) => {
-            Expression::Int(s.parse().expect("Expected integer"))
-        }
-        Token::LeftParen => {
-            let expr = parse_expression(tokens);
-            expect_token(tokens, Token::RightParen);
-            expr
-        }
-        Token::Identifier(s) => Expression::Variable(s),
-        Token::Minus | Token::LogicNot | Token::BitwiseNot => {
-            let expr = parse_factor(tokens);
-            let operator = match token {
-                Token::Minus => UnaryOperator::Negation,
-                Token::LogicNot => UnaryOperator::LogicNot,
-                Token::BitwiseNot => UnaryOperator::BitwiseNot,
-                _ => unreachable!(),
+    /// Records a declaration of `tag` in the current scope, which can't
+    /// already have a tag of a different kind with that name
+    fn declare_tag(&mut self, tag: &str, declared: Tag) {
+        let tags = &mut self.scopes.last_mut().unwrap().tags;
+        match (tags.get(tag), &declared) {
+            (Some(Tag::Enum(_)), Tag::Enum(_)) => panic!("Redefinition of enum {tag}"),
+            (Some(Tag::StructOrUnion), Tag::StructOrUnion) | (None, _) => {}
+            (Some(_), _) => panic!("{tag} redeclared as a different kind of tag"),
+        }
+        tags.insert(tag.to_string(), declared);
+    }
+
+    fn lookup_tag(&self, tag: &str) -> Option<&Tag> {
+        self.scopes.iter().rev().find_map(|scope| scope.tags.get(tag))
+    }
+
+    /// Whether `token` is a typedef name in the current scope
+    fn is_typedef_name(&self, token: Option<&Token>) -> bool {
+        matches!(token, Some(Token::Identifier(name)) if matches!(self.lookup(name), Some(Binding::Typedef(_))))
+    }
+
+    /// Whether the next token starts a declaration rather than a statement.
+    /// `T * x;` is a declaration only if `T` is a typedef name, and even
+    /// then `T:` is a label.
+    fn is_declaration_start(&self) -> bool {
+        let token = self.tokens.front();
+        is_type_specifier(token)
+            || (self.is_typedef_name(token) && self.tokens.get(1) != Some(&Token::Colon))
+            || matches!(token, Some(Token::Keyword(s)) if matches!(s.as_str(), "static" | "extern" | "typedef"))
+    }
+
+    fn expect_token(&mut self, expected: Token) {
+        assert_eq!(
+            self.tokens.pop_front().expect("Expected token {expected:?}"),
+            expected
+        );
+    }
+
+    fn parse_storage_class(&mut self) -> Option<StorageClass> {
+        let storage_class = match self.tokens.front() {
+            Some(Token::Keyword(s)) if s == "static" => StorageClass::Static,
+            Some(Token::Keyword(s)) if s == "extern" => StorageClass::Extern,
+            _ => return None,
+        };
+        self.tokens.pop_front();
+        Some(storage_class)
+    }
+
+    /// Parses a type specifier and any `*` that follow it
+    fn parse_type(&mut self) -> Type {
+        let mut result = if is_keyword(self.tokens.front(), "struct") || is_keyword(self.tokens.front(), "union") {
+            self.parse_struct_specifier()
+        } else if is_keyword(self.tokens.front(), "enum") {
+            self.parse_enum_specifier()
+        } else if self.is_typedef_name(self.tokens.front()) {
+            let Some(Token::Identifier(name)) = self.tokens.pop_front() else { unreachable!() };
+            let Some(Binding::Typedef(target)) = self.lookup(&name) else { unreachable!() };
+            target.clone()
+        } else {
+            self.parse_integer_specifier()
+        };
+        while self.tokens.front() == Some(&Token::Times) {
+            self.tokens.pop_front();
+            result = Type::pointer_to(result);
+        }
+        result
+    }
+
+    /// Parses `struct tag`, `struct tag { ... }` or `struct { ... }`, or the
+    /// same with `union`. Which struct a tag refers to is worked out by
+    /// semantic analysis.
+    fn parse_struct_specifier(&mut self) -> Type {
+        let is_union = self.tokens.pop_front() == Some(Token::Keyword("union".to_string()));
+        let tag = if let Some(Token::Identifier(tag)) = self.tokens.front() {
+            let tag = tag.clone();
+            self.tokens.pop_front();
+            Some(tag)
+        } else {
+            None
+        };
+        if let Some(tag) = &tag {
+            // A member list or `struct s;` declares the tag in the current
+            // scope, as does the first mention of it
+            let declares = matches!(self.tokens.front(), Some(Token::LeftBrace | Token::Semicolon));
+            match self.lookup_tag(tag) {
+                Some(Tag::Enum(_)) if !declares => panic!("{tag} redeclared as a different kind of tag"),
+                Some(_) if !declares => {}
+                _ => self.declare_tag(tag, Tag::StructOrUnion),
+            }
+        }
+        let definition = if self.tokens.front() == Some(&Token::LeftBrace) {
+            self.tokens.pop_front();
+            let mut members = Vec::new();
+            while self.tokens.front() != Some(&Token::RightBrace) {
+                let member_type = self.parse_type();
+                let Some(Token::Identifier(name)) = self.tokens.pop_front()
+                else { panic!("Expected member name in struct or union declaration") };
+                let member_type = self.parse_array_dimensions(member_type);
+                self.expect_token(Token::Semicolon);
+                members.push((name, member_type));
+            }
+            self.tokens.pop_front();
+            Some(members)
+        } else {
+            if tag.is_none() {
+                panic!("Expected tag or member list, found {:?}", self.tokens.front());
+            }
+            None
+        };
+        Type::Struct(Rc::new(StructType::new(is_union, tag, definition)))
+    }
+
+    /// Parses `enum tag`, `enum tag { ... }` or `enum { ... }`. Enumerators
+    /// are `int` constants numbered from zero or from their initializer.
+    /// Like gcc, the enum type itself is `unsigned int` unless one of them
+    /// is negative.
+    fn parse_enum_specifier(&mut self) -> Type {
+        self.tokens.pop_front();
+        let tag = if let Some(Token::Identifier(tag)) = self.tokens.front() {
+            let tag = tag.clone();
+            self.tokens.pop_front();
+            Some(tag)
+        } else {
+            None
+        };
+        if self.tokens.front() != Some(&Token::LeftBrace) {
+            let Some(tag) = tag else { panic!("Expected enum tag or enumerator list, found {:?}", self.tokens.front()) };
+            return match self.lookup_tag(&tag) {
+                Some(Tag::Enum(enum_type)) => enum_type.clone(),
+                Some(Tag::StructOrUnion) => panic!("{tag} redeclared as a different kind of tag"),
+                None => panic!("Use of undeclared enum {tag}"),
             };
-            Expression::UnaryOperation(
-                operator,
-                Box::new(expr)
-            )
         }
-        _ => panic!("Unexpected token {token:?}. Factor expected."),
+        self.tokens.pop_front();
+        let mut value = 0;
+        let mut any_negative = false;
+        while self.tokens.front() != Some(&Token::RightBrace) {
+            let Some(Token::Identifier(name)) = self.tokens.pop_front()
+            else { panic!("Expected enumerator name in enum declaration") };
+            if self.tokens.front() == Some(&Token::Assign) {
+                self.tokens.pop_front();
+                value = evaluate_constant(&self.parse_conditional_expression())
+                    .unwrap_or_else(|message| panic!("{message} in value of enumerator {name}"));
+            }
+            if i32::try_from(value).is_err() {
+                panic!("Value {value} of enumerator {name} is out of range of int");
+            }
+            any_negative |= value < 0;
+            self.declare(&name, Binding::Enumerator(value));
+            value += 1;
+            // A trailing comma is allowed
+            if self.tokens.front() != Some(&Token::RightBrace) {
+                self.expect_token(Token::Comma);
+            }
+        }
+        self.tokens.pop_front();
+        let enum_type = if any_negative { Type::Int } else { Type::UnsignedInt };
+        if let Some(tag) = tag {
+            self.declare_tag(&tag, Tag::Enum(enum_type.clone()));
+        }
+        enum_type
+    }
+
+    /// Parses the keywords of an integer type, which can come in any order as
+    /// in `long long int`.
+    fn parse_integer_specifier(&mut self) -> Type {
+        let mut specifiers = Vec::new();
+        while is_type_specifier(self.tokens.front()) {
+            let Some(Token::Keyword(s)) = self.tokens.pop_front() else { unreachable!() };
+            specifiers.push(s);
+        }
+        if specifiers.is_empty() {
+            panic!("Expected type, found {:?}", self.tokens.front());
+        }
+        let count = |name: &str| specifiers.iter().filter(|s| *s == name).count();
+        let invalid = || panic!("Invalid type specifier {}", specifiers.join(" "));
+        if count("signed") + count("unsigned") > 1 || count("int") > 1 {
+            invalid();
+        }
+        let mut result = match (count("char"), count("short"), count("int"), count("long")) {
+            (1, 0, 0, 0) => Type::Char,
+            (0, 1, _, 0) => Type::Short,
+            // Including a lone `signed` or `unsigned`
+            (0, 0, _, 0) => Type::Int,
+            (0, 0, _, 1) => Type::Long,
+            (0, 0, _, 2) => Type::LongLong,
+            _ => invalid(),
+        };
+        if count("unsigned") == 1 {
+            result = result.to_unsigned();
+        }
+        result
+    }
+
+    /// Parses the `[N]` suffixes of an array declarator. `int m[3][4]` is an
+    /// array of 3 arrays of 4 ints.
+    fn parse_array_dimensions(&mut self, element: Type) -> Type {
+        let mut lengths = Vec::new();
+        while self.tokens.front() == Some(&Token::LeftBracket) {
+            self.tokens.pop_front();
+            let length = evaluate_constant(&self.parse_expression())
+                .unwrap_or_else(|message| panic!("{message} in array size"));
+            if length <= 0 {
+                panic!("Array size must be positive, got {length}");
+            }
+            lengths.push(length);
+            self.expect_token(Token::RightBracket);
+        }
+        lengths.into_iter().rev().fold(element, |element, length| {
+            Type::Array(Box::new(element), length)
+        })
+    }
+
+    /// Parses `typedef type name;`, where `name` may be followed by array
+    /// dimensions, and declares the name.
+    fn parse_typedef(&mut self) -> (String, Type) {
+        self.tokens.pop_front();
+        let target = self.parse_type();
+        let Some(Token::Identifier(name)) = self.tokens.pop_front()
+        else { panic!("Expected name in typedef declaration") };
+        let target = self.parse_array_dimensions(target);
+        self.expect_token(Token::Semicolon);
+        self.declare(&name, Binding::Typedef(target.clone()));
+        (name, target)
+    }
+
+    fn parse_top_level_declaration(&mut self) -> Declaration {
+        if is_keyword(self.tokens.front(), "typedef") {
+            let (name, target) = self.parse_typedef();
+            return Declaration::Typedef(name, target);
+        }
+        let storage_class = self.parse_storage_class();
+        let decl_type = self.parse_type();
+        if self.tokens.front() == Some(&Token::Semicolon) {
+            self.tokens.pop_front();
+            return Declaration::Type(decl_type);
+        }
+        let name_tok = self.tokens.pop_front().expect("Expected declaration name");
+        let name = match name_tok {
+            Token::Identifier(s) => s,
+            _ => panic!("Unexpected token {name_tok:?}. Declaration name expected"),
+        };
+        if self.tokens.front() != Some(&Token::LeftParen) {
+            return Declaration::Variable(self.parse_variable_declaration(decl_type, name, storage_class));
+        }
+        Declaration::Function(self.parse_function_declaration(decl_type, name, storage_class))
+    }
+
+    fn parse_function_declaration(
+        &mut self,
+        return_type: Type,
+        name: String,
+        storage_class: Option<StorageClass>,
+    ) -> FunctionDeclaration {
+        self.declare(&name, Binding::Object);
+        self.expect_token(Token::LeftParen);
+        let has_prototype = self.tokens.front() != Some(&Token::RightParen);
+        // Parameters are in scope in the body
+        self.scopes.push(ParseScope::default());
+        let parameters = self.parse_parameters();
+        let body = if self.tokens.front() == Some(&Token::Semicolon) {
+            self.tokens.pop_front();
+            None
+        } else {
+            if parameters.iter().any(|p| p.name.is_none()) {
+                panic!("Parameter name omitted in definition of function {name}");
+            }
+            self.expect_token(Token::LeftBrace);
+            let body = self.parse_statements();
+            self.expect_token(Token::RightBrace);
+            Some(body)
+        };
+        self.scopes.pop();
+        FunctionDeclaration {
+            return_type,
+            name,
+            parameters,
+            has_prototype,
+            body,
+            storage_class,
+            linkage: None,
+        }
+    }
+
+    /// Parses a comma-separated list of parameters up to and including the
+    /// closing parenthesis. `(void)` is an empty list.
+    fn parse_parameters(&mut self) -> Vec<Parameter> {
+        let mut parameters = Vec::new();
+        if is_keyword(self.tokens.front(), "void") && self.tokens.get(1) == Some(&Token::RightParen) {
+            self.tokens.pop_front();
+        }
+        if self.tokens.front() == Some(&Token::RightParen) {
+            self.tokens.pop_front();
+            return parameters;
+        }
+        loop {
+            let mut param_type = self.parse_type();
+            let name = if let Some(Token::Identifier(name)) = self.tokens.front() {
+                let name = name.clone();
+                self.tokens.pop_front();
+                self.declare(&name, Binding::Object);
+                Some(name)
+            } else {
+                None
+            };
+            // Array parameters are really pointers, so their first dimension can
+            // be left out
+            if self.tokens.front() == Some(&Token::LeftBracket) && self.tokens.get(1) == Some(&Token::RightBracket) {
+                self.tokens.pop_front();
+                self.tokens.pop_front();
+                param_type = Type::pointer_to(self.parse_array_dimensions(param_type));
+            } else {
+                param_type = self.parse_array_dimensions(param_type).decay();
+            }
+            parameters.push(Parameter { param_type, name });
+            match self.tokens.pop_front() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => break,
+                token => panic!("Unexpected token {token:?} in parameter list"),
+            }
+        }
+        parameters
+    }
+
+    /// Parses the arguments of a call, after the opening parenthesis.
+    fn parse_arguments(&mut self) -> Vec<Expression> {
+        let mut arguments = Vec::new();
+        if self.tokens.front() == Some(&Token::RightParen) {
+            self.tokens.pop_front();
+            return arguments;
+        }
+        loop {
+            arguments.push(self.parse_expression());
+            match self.tokens.pop_front() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => break,
+                token => panic!("Unexpected token {token:?} in argument list"),
+            }
+        }
+        arguments
+    }
+
+    fn parse_statements(&mut self) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        while let Some(token) = self.tokens.front() {
+            if *token == Token::RightBrace {
+                break;
+            }
+            statements.push(self.parse_block_item());
+        }
+        statements
+    }
+
+    /// Parses either a declaration or a statement. Declarations are not
+    /// statements in C, so they can't be the body of an `if`.
+    fn parse_block_item(&mut self) -> Statement {
+        match self.tokens.front() {
+            _ if self.is_declaration_start() => self.parse_declaration(),
+            _ => self.parse_statement(),
+        }
+    }
+
+    fn parse_declaration(&mut self) -> Statement {
+        if is_keyword(self.tokens.front(), "typedef") {
+            let (name, target) = self.parse_typedef();
+            return Statement::Typedef(name, target);
+        }
+        let storage_class = self.parse_storage_class();
+        let var_type = self.parse_type();
+        if self.tokens.front() == Some(&Token::Semicolon) {
+            self.tokens.pop_front();
+            return Statement::DeclareType(var_type);
+        }
+        let Token::Identifier(name) = self.tokens.pop_front().expect("Expected variable name")
+        else { panic!("Unexpected token, identifier expected") };
+        Statement::Declare(self.parse_variable_declaration(var_type, name, storage_class))
+    }
+
+    /// Parses the array dimensions, optional initializer and the semicolon after
+    /// a variable name.
+    fn parse_variable_declaration(
+        &mut self,
+        var_type: Type,
+        name: String,
+        storage_class: Option<StorageClass>,
+    ) -> VariableDeclaration {
+        let var_type = self.parse_array_dimensions(var_type);
+        self.declare(&name, Binding::Object);
+        let initializer = if let Some(Token::Assign) = self.tokens.front() {
+            if matches!(var_type, Type::Array(..)) {
+                panic!("Array initializers are not supported");
+            }
+            self.tokens.pop_front();
+            Some(self.parse_expression())
+        } else {
+            None
+        };
+        self.expect_token(Token::Semicolon);
+        VariableDeclaration {
+            var_type,
+            name,
+            initializer,
+            storage_class,
+            linkage: None,
+        }
+    }
+
+    /// Parses an optional expression followed by `end`, as found in the clauses
+    /// of a `for` statement.
+    fn parse_optional_expression(&mut self, end: Token) -> Option<Expression> {
+        if self.tokens.front() == Some(&end) {
+            self.tokens.pop_front();
+            return None;
+        }
+        let expr = self.parse_expression();
+        self.expect_token(end);
+        Some(expr)
+    }
+
+    fn parse_statement(&mut self) -> Statement {
+        // println!("Function parse_statement called");
+        // println!("Tokens: {:?}", self.tokens);
+        let token = self.tokens.front().expect("Expected statement");
+        match token {
+            Token::LeftBrace => {
+                self.tokens.pop_front();
+                self.scopes.push(ParseScope::default());
+                let statements = self.parse_statements();
+                self.scopes.pop();
+                self.expect_token(Token::RightBrace);
+                Statement::Block(statements)
+            }
+            // The null statement, mostly found after a label
+            Token::Semicolon => {
+                self.tokens.pop_front();
+                Statement::Block(Vec::new())
+            }
+            Token::Keyword(s) => match s.as_str() {
+                "return" => {
+                    self.tokens.pop_front();
+                    let expr = self.parse_expression();
+                    self.expect_token(Token::Semicolon);
+                    Statement::Return(expr)
+                }
+                "if" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::LeftParen);
+                    let condition = self.parse_expression();
+                    self.expect_token(Token::RightParen);
+                    let then = self.parse_statement();
+                    // An `else` always belongs to the innermost `if` still open,
+                    // which is exactly the one we are parsing here.
+                    let otherwise = if is_keyword(self.tokens.front(), "else") {
+                        self.tokens.pop_front();
+                        Some(Box::new(self.parse_statement()))
+                    } else {
+                        None
+                    };
+                    Statement::If(condition, Box::new(then), otherwise)
+                }
+                "while" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::LeftParen);
+                    let condition = self.parse_expression();
+                    self.expect_token(Token::RightParen);
+                    let body = self.parse_statement();
+                    Statement::While(condition, Box::new(body))
+                }
+                "do" => {
+                    self.tokens.pop_front();
+                    let body = self.parse_statement();
+                    self.expect_token(Token::Keyword("while".to_string()));
+                    self.expect_token(Token::LeftParen);
+                    let condition = self.parse_expression();
+                    self.expect_token(Token::RightParen);
+                    self.expect_token(Token::Semicolon);
+                    Statement::DoWhile(Box::new(body), condition)
+                }
+                "for" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::LeftParen);
+                    self.scopes.push(ParseScope::default());
+                    let init = if self.is_declaration_start() {
+                        let declaration = self.parse_declaration();
+                        match &declaration {
+                            Statement::Declare(var_decl) if var_decl.storage_class.is_some() => panic!(
+                                "Declaration of {} in for loop initializer must be automatic",
+                                var_decl.name
+                            ),
+                            Statement::DeclareType(_) | Statement::Typedef(..) => panic!("Expected a variable declaration in for loop initializer"),
+                            _ => {}
+                        }
+                        Some(Box::new(declaration))
+                    } else {
+                        self.parse_optional_expression(Token::Semicolon)
+                            .map(|expr| Box::new(Statement::Expression(expr)))
+                    };
+                    let condition = self.parse_optional_expression(Token::Semicolon);
+                    let step = self.parse_optional_expression(Token::RightParen);
+                    let body = self.parse_statement();
+                    self.scopes.pop();
+                    Statement::For(init, condition, step, Box::new(body))
+                }
+                "switch" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::LeftParen);
+                    let condition = self.parse_expression();
+                    self.expect_token(Token::RightParen);
+                    let body = self.parse_statement();
+                    Statement::Switch(condition, Box::new(body))
+                }
+                "case" => {
+                    self.tokens.pop_front();
+                    let value = self.parse_conditional_expression();
+                    self.expect_token(Token::Colon);
+                    let stmt = self.parse_statement();
+                    Statement::Case(value, Box::new(stmt))
+                }
+                "default" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::Colon);
+                    let stmt = self.parse_statement();
+                    Statement::Default(Box::new(stmt))
+                }
+                "goto" => {
+                    self.tokens.pop_front();
+                    let Some(Token::Identifier(label)) = self.tokens.pop_front()
+                    else { panic!("Expected label name after goto") };
+                    self.expect_token(Token::Semicolon);
+                    Statement::Goto(label)
+                }
+                "break" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::Semicolon);
+                    Statement::Break
+                }
+                "continue" => {
+                    self.tokens.pop_front();
+                    self.expect_token(Token::Semicolon);
+                    Statement::Continue
+                }
+                _ => panic!("Keyword {s} not supported"),
+            }
+            // Labels have a namespace of their own
+            Token::Identifier(_) if self.tokens.get(1) == Some(&Token::Colon) => {
+                let Some(Token::Identifier(label)) = self.tokens.pop_front() else { unreachable!() };
+                self.tokens.pop_front();
+                let stmt = self.parse_statement();
+                Statement::Labeled(label, Box::new(stmt))
+            }
+            _ => {
+                let expr = self.parse_expression();
+                self.expect_token(Token::Semicolon);
+                Statement::Expression(expr)
+            }
+        }
+    }
+
+    fn parse_expression(&mut self) -> Expression {
+        let left = self.parse_conditional_expression();
+        let op = match self.tokens.front() {
+            Some(Token::Assign) => {
+                self.tokens.pop_front();
+                let right = self.parse_expression();
+                return Expression::new(ExpressionKind::BinaryOperation(
+                    Box::new(left),
+                    BinaryOperator::Assign,
+                    Box::new(right),
+                ));
+            }
+            Some(Token::PlusAssign) => BinaryOperator::Plus,
+            Some(Token::MinusAssign) => BinaryOperator::Minus,
+            Some(Token::TimesAssign) => BinaryOperator::Times,
+            Some(Token::DivideAssign) => BinaryOperator::Divide,
+            Some(Token::RemainderAssign) => BinaryOperator::Remainder,
+            Some(Token::ShiftLeftAssign) => BinaryOperator::ShiftLeft,
+            Some(Token::ShiftRightAssign) => BinaryOperator::ShiftRight,
+            Some(Token::BitwiseAndAssign) => BinaryOperator::BitwiseAnd,
+            Some(Token::BitwiseXorAssign) => BinaryOperator::BitwiseXor,
+            Some(Token::BitwiseOrAssign) => BinaryOperator::BitwiseOr,
+            _ => return left,
+        };
+        self.tokens.pop_front();
+        let right = self.parse_expression();
+        Expression::new(ExpressionKind::CompoundAssignment(op, Box::new(left), Box::new(right)))
+    }
+
+    fn parse_conditional_expression(&mut self) -> Expression {
+        let condition = self.parse_logic_or_expression();
+        match self.tokens.front() {
+            Some(Token::Question) => {
+                self.tokens.pop_front();
+                let then = self.parse_expression();
+                self.expect_token(Token::Colon);
+                let otherwise = self.parse_conditional_expression();
+                Expression::new(ExpressionKind::Conditional(
+                    Box::new(condition),
+                    Box::new(then),
+                    Box::new(otherwise),
+                ))
+            }
+            _ => condition,
+        }
+    }
+
+    parse_binary_operator!(parse_logic_or_expression, parse_logic_and_expr, Token::LogicOr);
+    parse_binary_operator!(parse_logic_and_expr, parse_bitwise_or_expr, Token::LogicAnd);
+    parse_binary_operator!(parse_bitwise_or_expr, parse_bitwise_xor_expr, Token::BitwiseOr);
+    parse_binary_operator!(parse_bitwise_xor_expr, parse_bitwise_and_expr, Token::BitwiseXor);
+    parse_binary_operator!(parse_bitwise_and_expr, parse_eq_expr, Token::BitwiseAnd);
+    parse_binary_operator!(parse_eq_expr, parse_rel_expr, Token::EQ | Token::NEQ);
+    parse_binary_operator!(parse_rel_expr, parse_shift_expr, Token::LT | Token::GT | Token::LE | Token::GE);
+    parse_binary_operator!(parse_shift_expr, parse_add_expr, Token::ShiftLeft | Token::ShiftRight);
+    parse_binary_operator!(parse_add_expr, parse_term, Token::Plus | Token::Minus);
+    parse_binary_operator!(parse_term, parse_factor, Token::Times | Token::Divide | Token::Remainder);
+
+    fn parse_factor(&mut self) -> Expression {
+        match self.tokens.front() {
+            Some(Token::Minus | Token::LogicNot | Token::BitwiseNot | Token::BitwiseAnd | Token::Times) => {
+                let token = self.tokens.pop_front().unwrap();
+                let expr = self.parse_factor();
+                let operator = match token {
+                    Token::Minus => UnaryOperator::Negation,
+                    Token::LogicNot => UnaryOperator::LogicNot,
+                    Token::BitwiseNot => UnaryOperator::BitwiseNot,
+                    Token::BitwiseAnd => UnaryOperator::AddressOf,
+                    Token::Times => UnaryOperator::Dereference,
+                    _ => unreachable!(),
+                };
+                Expression::new(ExpressionKind::UnaryOperation(
+                    operator,
+                    Box::new(expr)
+                ))
+            }
+            Some(Token::Increment | Token::Decrement) => {
+                let op = if self.tokens.pop_front() == Some(Token::Increment) {
+                    IncrementOperator::Increment
+                } else {
+                    IncrementOperator::Decrement
+                };
+                let expr = self.parse_factor();
+                Expression::new(ExpressionKind::Prefix(op, Box::new(expr)))
+            }
+            _ => self.parse_postfix_expression(),
+        }
+    }
+
+    fn parse_postfix_expression(&mut self) -> Expression {
+        let mut expr = self.parse_primary_expression();
+        loop {
+            match self.tokens.front() {
+                Some(Token::LeftBracket) => {
+                    self.tokens.pop_front();
+                    let index = self.parse_expression();
+                    self.expect_token(Token::RightBracket);
+                    // `a[i]` is defined as `*(a + i)`
+                    expr = Expression::new(ExpressionKind::UnaryOperation(
+                        UnaryOperator::Dereference,
+                        Box::new(Expression::new(ExpressionKind::BinaryOperation(
+                            Box::new(expr),
+                            BinaryOperator::Plus,
+                            Box::new(index),
+                        ))),
+                    ));
+                }
+                Some(Token::Dot | Token::Arrow) => {
+                    if self.tokens.pop_front() == Some(Token::Arrow) {
+                        expr = Expression::new(ExpressionKind::UnaryOperation(
+                            UnaryOperator::Dereference,
+                            Box::new(expr),
+                        ));
+                    }
+                    let Some(Token::Identifier(name)) = self.tokens.pop_front()
+                    else { panic!("Expected member name after . or ->") };
+                    expr = Expression::new(ExpressionKind::Member(Box::new(expr), name));
+                }
+                Some(Token::Increment | Token::Decrement) => {
+                    let op = if self.tokens.pop_front() == Some(Token::Increment) {
+                        IncrementOperator::Increment
+                    } else {
+                        IncrementOperator::Decrement
+                    };
+                    expr = Expression::new(ExpressionKind::Postfix(op, Box::new(expr)));
+                }
+                _ => return expr,
+            }
+        }
+    }
+
+    fn parse_primary_expression(&mut self) -> Expression {
+        let token = self.tokens.pop_front().expect("Expected a factor");
+        match token {
+            Token::Constant(s) => {
+                parse_integer_constant(&s)
+            }
+            // `char` is signed, and so are character constants
+            Token::CharLiteral(c) => Expression::typed(ExpressionKind::Int(c as i8 as i64), Type::Int),
+            Token::StringLiteral(mut s) => {
+                // Adjacent string literals are concatenated
+                while let Some(Token::StringLiteral(next)) = self.tokens.front() {
+                    s.extend_from_slice(next);
+                    self.tokens.pop_front();
+                }
+                Expression::new(ExpressionKind::String(s))
+            }
+            Token::LeftParen => {
+                let expr = self.parse_expression();
+                self.expect_token(Token::RightParen);
+                expr
+            }
+            Token::Identifier(s) => {
+                if self.tokens.front() == Some(&Token::LeftParen) {
+                    self.tokens.pop_front();
+                    Expression::new(ExpressionKind::FunctionCall(s, self.parse_arguments()))
+                } else {
+                    match self.lookup(&s) {
+                        Some(Binding::Enumerator(value)) => Expression::typed(ExpressionKind::Int(*value), Type::Int),
+                        Some(Binding::Typedef(_)) => panic!("Unexpected type name {s} in expression"),
+                        _ => Expression::new(ExpressionKind::Variable(s)),
+                    }
+                }
+            }
+            _ => panic!("Unexpected token {token:?}. Factor expected."),
+        }
     }
 }
@@ -0,0 +1,859 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::parser::*;
+use crate::types::{StructType, Type};
+
+/// What an identifier in the ordinary namespace refers to
+enum Symbol {
+    Variable(Type),
+    Function {
+        return_type: Type,
+        /// `None` until the function is declared with a prototype
+        parameters: Option<Vec<Type>>,
+        defined: bool,
+    },
+}
+
+/// Checks a program between parsing and code generation. Every identifier
+/// must refer to a declaration in scope, struct tags are resolved to the
+/// struct they name, and every expression gets a type.
+/// The conversions C performs implicitly (integer promotions, the usual
+/// arithmetic conversions, array decay and conversion on assignment) become
+/// `Convert` nodes, so the code generator never has to work them out.
+/// Returns the checked program along with any warnings about it.
+pub fn analyze(program: Program) -> Result<(Program, Vec<String>), String> {
+    let mut analyzer = Analyzer {
+        scopes: vec![HashMap::new()],
+        tags: vec![HashMap::new()],
+        return_type: Type::Int,
+        loop_depth: 0,
+        switches: Vec::new(),
+        labels: HashSet::new(),
+        goto_targets: HashSet::new(),
+        linkage: HashMap::new(),
+        linked_types: HashMap::new(),
+        initialized: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    let mut declarations = Vec::new();
+    for declaration in program.declarations {
+        declarations.push(match declaration {
+            Declaration::Function(func_decl) => {
+                let name = func_decl.name.clone();
+                let func_decl = analyzer.analyze_function(func_decl)
+                    .map_err(|message| format!("In function {}: {}", name, message))?;
+                Declaration::Function(func_decl)
+            }
+            Declaration::Variable(var_decl) => {
+                Declaration::Variable(analyzer.analyze_global_variable(var_decl)?)
+            }
+            Declaration::Type(declared) => Declaration::Type(analyzer.declare_type(declared)?),
+            Declaration::Typedef(name, target) => Declaration::Typedef(name, analyzer.resolve_type(&target)?),
+        });
+    }
+    Ok((Program { declarations }, analyzer.warnings))
+}
+
+struct Analyzer {
+    /// Innermost scope last. The first one is file scope.
+    scopes: Vec<HashMap<String, Symbol>>,
+    /// Struct and union tags, which have their own namespace but the same
+    /// scoping
+    tags: Vec<HashMap<String, Rc<StructType>>>,
+    /// Return type of the function being analyzed
+    return_type: Type,
+    /// Number of loops around the current statement
+    loop_depth: usize,
+    /// The `switch` statements around the current statement, innermost last
+    switches: Vec<SwitchCases>,
+    /// Labels defined in the function being analyzed, which are visible
+    /// throughout it
+    labels: HashSet<String>,
+    /// Labels named by a `goto` in the function being analyzed
+    goto_targets: HashSet<String>,
+    /// Linkage of every identifier declared at file scope or `extern`
+    linkage: HashMap<String, Linkage>,
+    /// Type of every variable with linkage, including those only declared
+    /// `extern` inside a block, whose declarations must all agree
+    linked_types: HashMap<String, Type>,
+    /// Variables with linkage that have been given an initializer
+    initialized: HashSet<String>,
+    warnings: Vec<String>,
+}
+
+/// The labels seen so far in the body of a `switch`
+struct SwitchCases {
+    /// Promoted type of the controlling expression, which case values are
+    /// converted to
+    switch_type: Type,
+    values: Vec<i64>,
+    has_default: bool,
+}
+
+fn symbol(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Plus => "+",
+        BinaryOperator::Minus => "-",
+        BinaryOperator::Times => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Remainder => "%",
+        BinaryOperator::ShiftLeft => "<<",
+        BinaryOperator::ShiftRight => ">>",
+        BinaryOperator::BitwiseAnd => "&",
+        BinaryOperator::BitwiseXor => "^",
+        BinaryOperator::BitwiseOr => "|",
+        BinaryOperator::LogicAnd => "&&",
+        BinaryOperator::LogicOr => "||",
+        BinaryOperator::EQ => "==",
+        BinaryOperator::NEQ => "!=",
+        BinaryOperator::LT => "<",
+        BinaryOperator::GT => ">",
+        BinaryOperator::LE => "<=",
+        BinaryOperator::GE => ">=",
+        BinaryOperator::Assign => "=",
+    }
+}
+
+fn unary_symbol(op: UnaryOperator) -> &'static str {
+    match op {
+        UnaryOperator::Negation => "-",
+        UnaryOperator::BitwiseNot => "~",
+        UnaryOperator::LogicNot => "!",
+        UnaryOperator::AddressOf => "&",
+        UnaryOperator::Dereference => "*",
+    }
+}
+
+/// Wraps `expr` in a conversion to `to`, unless it already has that type
+fn convert(expr: Expression, to: &Type) -> Expression {
+    if expr.expr_type() == to {
+        return expr;
+    }
+    Expression::typed(ExpressionKind::Convert(Box::new(expr)), to.clone())
+}
+
+/// Arrays used as values become pointers to their first element
+fn decay(expr: Expression) -> Expression {
+    let decayed = expr.expr_type().decay();
+    convert(expr, &decayed)
+}
+
+fn is_lvalue(expr: &Expression) -> bool {
+    match &expr.kind {
+        ExpressionKind::Variable(_) | ExpressionKind::UnaryOperation(UnaryOperator::Dereference, _) => true,
+        ExpressionKind::Member(base, _) => is_lvalue(base),
+        _ => false,
+    }
+}
+
+/// An integer constant expression with value 0, which converts to any
+/// pointer type
+fn is_null_pointer_constant(expr: &Expression) -> bool {
+    expr.expr_type().is_integer() && evaluate_constant(expr) == Ok(0)
+}
+
+/// Converts `value` to `target` as assignment does: between any integer
+/// types, and to a pointer only from the same pointer type or a null
+/// pointer constant.
+fn convert_for_assignment(value: Expression, target: &Type) -> Result<Expression, String> {
+    let from = value.expr_type();
+    if from == target
+        || (from.is_integer() && target.is_integer())
+        || (target.is_pointer() && is_null_pointer_constant(&value))
+    {
+        Ok(convert(value, target))
+    } else {
+        Err(format!("Incompatible types: cannot convert {} to {}", from, target))
+    }
+}
+
+/// Pointer arithmetic scales by the size of the pointee, so it has to be
+/// complete
+fn check_pointer_arithmetic(pointer_type: &Type) -> Result<(), String> {
+    match pointer_type.pointee() {
+        Some(target) if !target.is_complete() => {
+            Err(format!("Arithmetic on pointer to incomplete type {}", target))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the initializer of a variable with static storage by its value
+fn fold_constant(initializer: Expression) -> Result<Expression, String> {
+    let value = evaluate_constant(&initializer)?;
+    Ok(Expression::typed(ExpressionKind::Int(value), initializer.expr_type().clone()))
+}
+
+impl Analyzer {
+    fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.tags.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.tags.pop();
+    }
+
+    /// Replaces the struct types written in a declaration by the structs
+    /// their tags refer to in the current scope, defining any struct whose
+    /// members are given.
+    fn resolve_type(&mut self, written: &Type) -> Result<Type, String> {
+        match written {
+            Type::Pointer(target) => Ok(Type::pointer_to(self.resolve_type(target)?)),
+            Type::Array(element, length) => {
+                let element = self.resolve_type(element)?;
+                if !element.is_complete() {
+                    return Err(format!("Array has incomplete element type {}", element));
+                }
+                Ok(Type::Array(Box::new(element), *length))
+            }
+            Type::Struct(struct_type) => self.resolve_struct(struct_type),
+            _ => Ok(written.clone()),
+        }
+    }
+
+    fn resolve_struct(&mut self, struct_type: &Rc<StructType>) -> Result<Type, String> {
+        // Already defined, for instance when the same specifier is resolved
+        // again
+        if struct_type.is_complete() {
+            return Ok(Type::Struct(struct_type.clone()));
+        }
+        let Some(definition) = &struct_type.definition else {
+            let tag = struct_type.tag.as_ref().expect("Struct reference without a tag");
+            if let Some(found) = self.tags.iter().rev().find_map(|tags| tags.get(tag)) {
+                if found.is_union != struct_type.is_union {
+                    return Err(format!("{:?} does not match earlier declaration as {:?}", struct_type, found));
+                }
+                return Ok(Type::Struct(found.clone()));
+            }
+            // The first mention of a tag declares an incomplete struct
+            self.tags.last_mut().unwrap().insert(tag.clone(), struct_type.clone());
+            return Ok(Type::Struct(struct_type.clone()));
+        };
+        // A definition completes an earlier declaration in the same scope
+        let mut defined = struct_type.clone();
+        if let Some(tag) = &struct_type.tag {
+            if let Some(previous) = self.tags.last().unwrap().get(tag) {
+                if previous.is_union != struct_type.is_union {
+                    return Err(format!("{:?} does not match earlier declaration as {:?}", struct_type, previous));
+                }
+                if previous.is_complete() {
+                    return Err(format!("Redefinition of {:?}", struct_type));
+                }
+                defined = previous.clone();
+            }
+            // Members can point to the struct being defined
+            self.tags.last_mut().unwrap().insert(tag.clone(), defined.clone());
+        }
+        if definition.is_empty() {
+            return Err(format!("{:?} has no members", struct_type));
+        }
+        let mut members: Vec<(String, Type)> = Vec::new();
+        for (name, member_type) in definition {
+            let member_type = self.resolve_type(member_type)?;
+            if !member_type.is_complete() {
+                return Err(format!("Member {} has incomplete type {}", name, member_type));
+            }
+            if members.iter().any(|(previous, _)| previous == name) {
+                return Err(format!("Duplicate member {} in {:?}", name, struct_type));
+            }
+            members.push((name.clone(), member_type));
+        }
+        defined.complete(members);
+        Ok(Type::Struct(defined))
+    }
+
+    /// Handles a declaration like `struct s;` or `struct s { ... };`
+    fn declare_type(&mut self, declared: Type) -> Result<Type, String> {
+        if let Type::Struct(struct_type) = &declared {
+            if struct_type.definition.is_none() {
+                // `struct s;` declares a new struct even if an outer scope
+                // has one with the same tag
+                let tag = struct_type.tag.clone().unwrap();
+                let tags = self.tags.last_mut().unwrap();
+                let current = tags.entry(tag).or_insert_with(|| struct_type.clone());
+                if current.is_union != struct_type.is_union {
+                    return Err(format!("{:?} does not match earlier declaration as {:?}", struct_type, current));
+                }
+                return Ok(Type::Struct(current.clone()));
+            }
+        }
+        self.resolve_type(&declared)
+    }
+
+    /// Resolves the type of a variable, which must be complete unless the
+    /// variable is defined elsewhere
+    fn resolve_variable_type(&mut self, var_decl: &VariableDeclaration) -> Result<Type, String> {
+        let var_type = self.resolve_type(&var_decl.var_type)?;
+        if !var_type.is_complete() && var_decl.storage_class != Some(StorageClass::Extern) {
+            return Err(format!("Variable {} has incomplete type {}", var_decl.name, var_type));
+        }
+        Ok(var_type)
+    }
+
+    /// Declares a variable in the innermost scope
+    fn declare_variable(&mut self, name: &str, var_type: Type) -> Result<(), String> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.contains_key(name) {
+            return Err(format!("Redeclaration of {}", name));
+        }
+        scope.insert(name.to_string(), Symbol::Variable(var_type));
+        Ok(())
+    }
+
+    /// Works out the linkage of a declaration at file scope or with
+    /// `extern`, checking it against earlier declarations of the same name.
+    fn declare_linkage(&mut self, name: &str, storage_class: Option<StorageClass>, is_function: bool)
+        -> Result<Linkage, String> {
+        let previous = self.linkage.get(name).copied();
+        let linkage = match (storage_class, previous) {
+            (Some(StorageClass::Static), Some(Linkage::External)) => {
+                return Err(format!("Static declaration of {} follows non-static declaration", name));
+            }
+            (Some(StorageClass::Static), _) => Linkage::Internal,
+            (Some(StorageClass::Extern), Some(previous)) => previous,
+            // Functions declared without a storage class behave as `extern`
+            (None, Some(Linkage::Internal)) if is_function => Linkage::Internal,
+            (None, Some(Linkage::Internal)) => {
+                return Err(format!("Non-static declaration of {} follows static declaration", name));
+            }
+            _ => Linkage::External,
+        };
+        self.linkage.insert(name.to_string(), linkage);
+        Ok(linkage)
+    }
+
+    /// Records the type of a variable with linkage, which must agree with
+    /// every other declaration of it
+    fn declare_linked_variable(&mut self, name: &str, var_type: &Type) -> Result<(), String> {
+        if let Some(previous) = self.linked_types.get(name) {
+            if previous != var_type {
+                return Err(format!("Conflicting types for {}", name));
+            }
+        }
+        self.linked_types.insert(name.to_string(), var_type.clone());
+        Ok(())
+    }
+
+    fn declare_function(&mut self, func_decl: &mut FunctionDeclaration) -> Result<(), String> {
+        let parameters = func_decl.has_prototype
+            .then(|| func_decl.parameters.iter().map(|p| p.param_type.clone()).collect::<Vec<_>>());
+        let defined = func_decl.body.is_some();
+        if self.linked_types.contains_key(&func_decl.name) {
+            return Err(format!("{} redeclared as a different kind of symbol", func_decl.name));
+        }
+        func_decl.linkage = Some(self.declare_linkage(&func_decl.name, func_decl.storage_class, true)?);
+        match self.scopes[0].get_mut(&func_decl.name) {
+            Some(Symbol::Variable(_)) => {
+                Err(format!("{} redeclared as a different kind of symbol", func_decl.name))
+            }
+            Some(Symbol::Function { return_type, parameters: previous, defined: previous_defined }) => {
+                let same_parameters = match (&*previous, &parameters) {
+                    (Some(previous), Some(parameters)) => previous == parameters,
+                    _ => true,
+                };
+                if *return_type != func_decl.return_type || !same_parameters {
+                    return Err(format!("Conflicting types for function {}", func_decl.name));
+                }
+                if *previous_defined && defined {
+                    return Err(format!("Redefinition of function {}", func_decl.name));
+                }
+                *previous_defined |= defined;
+                if previous.is_none() {
+                    *previous = parameters;
+                }
+                Ok(())
+            }
+            None => {
+                let symbol = Symbol::Function {
+                    return_type: func_decl.return_type.clone(),
+                    parameters,
+                    defined,
+                };
+                self.scopes[0].insert(func_decl.name.clone(), symbol);
+                Ok(())
+            }
+        }
+    }
+
+    fn analyze_function(&mut self, mut func_decl: FunctionDeclaration) -> Result<FunctionDeclaration, String> {
+        func_decl.return_type = self.resolve_type(&func_decl.return_type)?;
+        if matches!(func_decl.return_type, Type::Struct(_)) {
+            return Err("Returning structs or unions by value is not supported".to_string());
+        }
+        for parameter in &mut func_decl.parameters {
+            parameter.param_type = self.resolve_type(&parameter.param_type)?;
+            if matches!(parameter.param_type, Type::Struct(_)) {
+                return Err("Passing structs or unions by value is not supported".to_string());
+            }
+        }
+        self.declare_function(&mut func_decl)?;
+        let Some(body) = func_decl.body.take() else { return Ok(func_decl) };
+        self.return_type = func_decl.return_type.clone();
+        // Parameters live in the same scope as the outermost block of the body
+        self.push_scope();
+        for parameter in &func_decl.parameters {
+            if let Some(name) = &parameter.name {
+                self.declare_variable(name, parameter.param_type.clone())?;
+            }
+        }
+        self.labels.clear();
+        self.goto_targets.clear();
+        func_decl.body = Some(self.analyze_statements(body)?);
+        self.pop_scope();
+        let mut undefined: Vec<&String> = self.goto_targets.difference(&self.labels).collect();
+        undefined.sort();
+        if let Some(label) = undefined.first() {
+            return Err(format!("Label {} used but not defined", label));
+        }
+        let mut unused: Vec<&String> = self.labels.difference(&self.goto_targets).collect();
+        unused.sort();
+        for label in unused {
+            self.warnings.push(format!("In function {}: Label {} defined but not used", func_decl.name, label));
+        }
+        Ok(func_decl)
+    }
+
+    fn analyze_global_variable(&mut self, mut var_decl: VariableDeclaration) -> Result<VariableDeclaration, String> {
+        var_decl.var_type = self.resolve_variable_type(&var_decl)?;
+        if let Some(Symbol::Function { .. }) = self.scopes[0].get(&var_decl.name) {
+            return Err(format!("{} redeclared as a different kind of symbol", var_decl.name));
+        }
+        var_decl.linkage = Some(self.declare_linkage(&var_decl.name, var_decl.storage_class, false)?);
+        self.declare_linked_variable(&var_decl.name, &var_decl.var_type)?;
+        self.scopes[0].insert(var_decl.name.clone(), Symbol::Variable(var_decl.var_type.clone()));
+        if let Some(initializer) = var_decl.initializer.take() {
+            if !self.initialized.insert(var_decl.name.clone()) {
+                return Err(format!("Redefinition of {}", var_decl.name));
+            }
+            let initializer = self.analyze_value(initializer)?;
+            let initializer = convert_for_assignment(initializer, &var_decl.var_type)?;
+            var_decl.initializer = Some(fold_constant(initializer)
+                .map_err(|message| format!("{} for {}", message, var_decl.name))?);
+        }
+        Ok(var_decl)
+    }
+
+    fn analyze_local_variable(&mut self, mut var_decl: VariableDeclaration) -> Result<VariableDeclaration, String> {
+        var_decl.var_type = self.resolve_variable_type(&var_decl)?;
+        if var_decl.storage_class == Some(StorageClass::Extern) {
+            if var_decl.initializer.is_some() {
+                return Err(format!("Block scope extern declaration of {} has an initializer", var_decl.name));
+            }
+            if let Some(Symbol::Function { .. }) = self.scopes[0].get(&var_decl.name) {
+                return Err(format!("{} redeclared as a different kind of symbol", var_decl.name));
+            }
+            var_decl.linkage = Some(self.declare_linkage(&var_decl.name, var_decl.storage_class, false)?);
+            self.declare_linked_variable(&var_decl.name, &var_decl.var_type)?;
+        }
+        // The variable is in scope in its own initializer
+        self.declare_variable(&var_decl.name, var_decl.var_type.clone())?;
+        if let Some(initializer) = var_decl.initializer.take() {
+            let initializer = self.analyze_value(initializer)?;
+            let mut initializer = convert_for_assignment(initializer, &var_decl.var_type)?;
+            if var_decl.storage_class == Some(StorageClass::Static) {
+                initializer = fold_constant(initializer)
+                    .map_err(|message| format!("{} for {}", message, var_decl.name))?;
+            }
+            var_decl.initializer = Some(initializer);
+        }
+        Ok(var_decl)
+    }
+
+    fn analyze_statements(&mut self, statements: Vec<Statement>) -> Result<Vec<Statement>, String> {
+        statements.into_iter().map(|stmt| self.analyze_statement(stmt)).collect()
+    }
+
+    fn analyze_loop_body(&mut self, body: Statement) -> Result<Box<Statement>, String> {
+        self.loop_depth += 1;
+        let body = self.analyze_statement(body)?;
+        self.loop_depth -= 1;
+        Ok(Box::new(body))
+    }
+
+    fn analyze_statement(&mut self, stmt: Statement) -> Result<Statement, String> {
+        Ok(match stmt {
+            Statement::Return(expr) => {
+                let expr = self.analyze_value(expr)?;
+                Statement::Return(convert_for_assignment(expr, &self.return_type)?)
+            }
+            Statement::Block(statements) => {
+                self.push_scope();
+                let statements = self.analyze_statements(statements)?;
+                self.pop_scope();
+                Statement::Block(statements)
+            }
+            Statement::Declare(var_decl) => Statement::Declare(self.analyze_local_variable(var_decl)?),
+            Statement::DeclareType(declared) => Statement::DeclareType(self.declare_type(declared)?),
+            Statement::Typedef(name, target) => Statement::Typedef(name, self.resolve_type(&target)?),
+            Statement::Expression(expr) => Statement::Expression(self.analyze_value(expr)?),
+            Statement::If(condition, then, otherwise) => {
+                let condition = self.analyze_scalar(condition)?;
+                let then = Box::new(self.analyze_statement(*then)?);
+                let otherwise = match otherwise {
+                    Some(otherwise) => Some(Box::new(self.analyze_statement(*otherwise)?)),
+                    None => None,
+                };
+                Statement::If(condition, then, otherwise)
+            }
+            Statement::While(condition, body) => {
+                let condition = self.analyze_scalar(condition)?;
+                Statement::While(condition, self.analyze_loop_body(*body)?)
+            }
+            Statement::DoWhile(body, condition) => {
+                let body = self.analyze_loop_body(*body)?;
+                Statement::DoWhile(body, self.analyze_scalar(condition)?)
+            }
+            Statement::For(init, condition, step, body) => {
+                self.push_scope();
+                let init = match init {
+                    Some(init) => Some(Box::new(self.analyze_statement(*init)?)),
+                    None => None,
+                };
+                let condition = condition.map(|c| self.analyze_scalar(c)).transpose()?;
+                let step = step.map(|s| self.analyze_value(s)).transpose()?;
+                let body = self.analyze_loop_body(*body)?;
+                self.pop_scope();
+                Statement::For(init, condition, step, body)
+            }
+            Statement::Switch(condition, body) => {
+                let condition = self.analyze_value(condition)?;
+                if !condition.expr_type().is_integer() {
+                    return Err(format!("Switch quantity has type {}, not an integer type", condition.expr_type()));
+                }
+                let switch_type = condition.expr_type().promote();
+                let condition = convert(condition, &switch_type);
+                self.switches.push(SwitchCases { switch_type, values: Vec::new(), has_default: false });
+                let body = self.analyze_statement(*body);
+                self.switches.pop();
+                Statement::Switch(condition, Box::new(body?))
+            }
+            Statement::Case(value, stmt) => {
+                let value = self.analyze_value(value)?;
+                let Some(cases) = self.switches.last_mut() else {
+                    return Err("case label not within a switch statement".to_string());
+                };
+                if !value.expr_type().is_integer() {
+                    return Err("case label does not reduce to an integer constant".to_string());
+                }
+                let value = evaluate_constant(&value)
+                    .map_err(|_| "case label does not reduce to an integer constant".to_string())?;
+                // The label is compared as a value of the promoted type
+                let value = cases.switch_type.wrap(value);
+                if cases.values.contains(&value) {
+                    return Err(format!("Duplicate case value {}", value));
+                }
+                cases.values.push(value);
+                let value = Expression::typed(ExpressionKind::Int(value), cases.switch_type.clone());
+                Statement::Case(value, Box::new(self.analyze_statement(*stmt)?))
+            }
+            Statement::Default(stmt) => {
+                let Some(cases) = self.switches.last_mut() else {
+                    return Err("default label not within a switch statement".to_string());
+                };
+                if cases.has_default {
+                    return Err("Multiple default labels in one switch".to_string());
+                }
+                cases.has_default = true;
+                Statement::Default(Box::new(self.analyze_statement(*stmt)?))
+            }
+            Statement::Labeled(label, stmt) => {
+                if !self.labels.insert(label.clone()) {
+                    return Err(format!("Duplicate label {}", label));
+                }
+                Statement::Labeled(label, Box::new(self.analyze_statement(*stmt)?))
+            }
+            Statement::Goto(label) => {
+                self.goto_targets.insert(label.clone());
+                Statement::Goto(label)
+            }
+            Statement::Break if self.loop_depth == 0 && self.switches.is_empty() => {
+                return Err("break statement not within loop or switch".to_string());
+            }
+            Statement::Continue if self.loop_depth == 0 => {
+                return Err("continue statement not within a loop".to_string());
+            }
+            Statement::Break | Statement::Continue => stmt,
+        })
+    }
+
+    /// Analyzes an expression whose value is used, decaying arrays
+    fn analyze_value(&mut self, expr: Expression) -> Result<Expression, String> {
+        Ok(decay(self.analyze_expr(expr)?))
+    }
+
+    /// Analyzes an expression used as a condition
+    fn analyze_scalar(&mut self, expr: Expression) -> Result<Expression, String> {
+        let expr = self.analyze_value(expr)?;
+        let expr_type = expr.expr_type();
+        if !expr_type.is_integer() && !expr_type.is_pointer() {
+            return Err(format!("Expected a scalar value, found {}", expr_type));
+        }
+        Ok(expr)
+    }
+
+    fn analyze_expr(&mut self, expr: Expression) -> Result<Expression, String> {
+        Ok(match expr.kind {
+            // Constants are typed by the parser
+            ExpressionKind::Int(_) => expr,
+            ExpressionKind::String(s) => {
+                let string_type = Type::Array(Box::new(Type::Char), s.len() as i64 + 1);
+                Expression::typed(ExpressionKind::String(s), string_type)
+            }
+            ExpressionKind::Variable(name) => match self.lookup(&name) {
+                Some(Symbol::Variable(var_type)) => {
+                    let var_type = var_type.clone();
+                    Expression::typed(ExpressionKind::Variable(name), var_type)
+                }
+                Some(Symbol::Function { .. }) => {
+                    return Err(format!("Function {} used as a value, but function pointers are not supported", name));
+                }
+                None => return Err(format!("Use of undeclared identifier {}", name)),
+            },
+            ExpressionKind::UnaryOperation(op, operand) => self.analyze_unary(op, *operand)?,
+            ExpressionKind::BinaryOperation(left, BinaryOperator::Assign, right) => {
+                let target = self.analyze_assignment_target(*left)?;
+                let target_type = target.expr_type().clone();
+                let value = self.analyze_value(*right)?;
+                let value = convert_for_assignment(value, &target_type)?;
+                let kind = ExpressionKind::BinaryOperation(Box::new(target), BinaryOperator::Assign, Box::new(value));
+                Expression::typed(kind, target_type)
+            }
+            ExpressionKind::Prefix(op, operand) => {
+                let operand = self.analyze_increment_operand(*operand)?;
+                let operand_type = operand.expr_type().clone();
+                Expression::typed(ExpressionKind::Prefix(op, Box::new(operand)), operand_type)
+            }
+            ExpressionKind::Postfix(op, operand) => {
+                let operand = self.analyze_increment_operand(*operand)?;
+                let operand_type = operand.expr_type().clone();
+                Expression::typed(ExpressionKind::Postfix(op, Box::new(operand)), operand_type)
+            }
+            ExpressionKind::CompoundAssignment(op, left, right) => {
+                let target = self.analyze_assignment_target(*left)?;
+                let target_type = target.expr_type().clone();
+                let value = self.analyze_value(*right)?;
+                let value_type = value.expr_type().clone();
+                let value = if target_type.is_pointer()
+                    && matches!(op, BinaryOperator::Plus | BinaryOperator::Minus)
+                    && value_type.is_integer()
+                {
+                    check_pointer_arithmetic(&target_type)?;
+                    value
+                } else if !target_type.is_integer() || !value_type.is_integer() {
+                    return Err(format!("Invalid operands to {}= ({} and {})", symbol(op), target_type, value_type));
+                } else if matches!(op, BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight) {
+                    let promoted = value_type.promote();
+                    convert(value, &promoted)
+                } else {
+                    convert(value, &Type::common(&target_type, &value_type))
+                };
+                let kind = ExpressionKind::CompoundAssignment(op, Box::new(target), Box::new(value));
+                Expression::typed(kind, target_type)
+            }
+            ExpressionKind::BinaryOperation(left, op, right) => self.analyze_binary(*left, op, *right)?,
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                let condition = self.analyze_scalar(*condition)?;
+                let then = self.analyze_value(*then)?;
+                let otherwise = self.analyze_value(*otherwise)?;
+                let then_type = then.expr_type().clone();
+                let otherwise_type = otherwise.expr_type().clone();
+                let result_type = if then_type.is_integer() && otherwise_type.is_integer() {
+                    Type::common(&then_type, &otherwise_type)
+                } else if then_type == otherwise_type
+                    || (then_type.is_pointer() && is_null_pointer_constant(&otherwise))
+                {
+                    then_type
+                } else if otherwise_type.is_pointer() && is_null_pointer_constant(&then) {
+                    otherwise_type
+                } else {
+                    return Err(format!(
+                        "Type mismatch in conditional expression ({} and {})",
+                        then_type, otherwise_type
+                    ));
+                };
+                let kind = ExpressionKind::Conditional(
+                    Box::new(condition),
+                    Box::new(convert(then, &result_type)),
+                    Box::new(convert(otherwise, &result_type)),
+                );
+                Expression::typed(kind, result_type)
+            }
+            ExpressionKind::FunctionCall(name, arguments) => {
+                let (return_type, parameters) = match self.lookup(&name) {
+                    Some(Symbol::Function { return_type, parameters, .. }) => {
+                        (return_type.clone(), parameters.clone())
+                    }
+                    Some(Symbol::Variable(var_type)) => {
+                        return Err(format!("Called object {} of type {} is not a function", name, var_type));
+                    }
+                    None => return Err(format!("Implicit declaration of function {}", name)),
+                };
+                let arguments = arguments.into_iter()
+                    .map(|argument| self.analyze_value(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if arguments.iter().any(|argument| matches!(argument.expr_type(), Type::Struct(_))) {
+                    return Err("Passing structs or unions by value is not supported".to_string());
+                }
+                let arguments = match parameters {
+                    Some(parameters) => {
+                        if parameters.len() != arguments.len() {
+                            return Err(format!(
+                                "Function {} expects {} arguments, got {}",
+                                name, parameters.len(), arguments.len()
+                            ));
+                        }
+                        arguments.into_iter().zip(&parameters)
+                            .map(|(argument, param_type)| convert_for_assignment(argument, param_type))
+                            .collect::<Result<Vec<_>, _>>()?
+                    }
+                    // Without a prototype, arguments only undergo promotion
+                    None => arguments.into_iter()
+                        .map(|argument| {
+                            let promoted = argument.expr_type().promote();
+                            convert(argument, &promoted)
+                        })
+                        .collect(),
+                };
+                Expression::typed(ExpressionKind::FunctionCall(name, arguments), return_type)
+            }
+            ExpressionKind::Member(base, name) => {
+                let base = self.analyze_expr(*base)?;
+                let Type::Struct(struct_type) = base.expr_type()
+                else { return Err(format!("Member reference base type {} is not a struct or union", base.expr_type())) };
+                let Some(member) = struct_type.member(&name)
+                else { return Err(format!("No member named {} in {}", name, base.expr_type())) };
+                Expression::typed(ExpressionKind::Member(Box::new(base), name), member.member_type)
+            }
+            ExpressionKind::Convert(_) => unreachable!("Conversions are only inserted by semantic analysis"),
+        })
+    }
+
+    /// Analyzes the left operand of an assignment, which must be an lvalue
+    /// other than an array
+    fn analyze_assignment_target(&mut self, target: Expression) -> Result<Expression, String> {
+        let target = self.analyze_expr(target)?;
+        if !is_lvalue(&target) {
+            return Err("Expression is not assignable".to_string());
+        }
+        if matches!(target.expr_type(), Type::Array(..)) {
+            return Err(format!("Cannot assign to an array of type {}", target.expr_type()));
+        }
+        if !target.expr_type().is_complete() {
+            return Err(format!("Cannot assign to a value of incomplete type {}", target.expr_type()));
+        }
+        Ok(target)
+    }
+
+    /// Analyzes the operand of `++` or `--`, which is assigned to and must
+    /// be an integer or a pointer to a complete type
+    fn analyze_increment_operand(&mut self, operand: Expression) -> Result<Expression, String> {
+        let operand = self.analyze_assignment_target(operand)?;
+        let operand_type = operand.expr_type();
+        let valid = match operand_type.pointee() {
+            Some(target) => target.is_complete(),
+            None => operand_type.is_integer(),
+        };
+        if !valid {
+            return Err(format!("Cannot increment or decrement a value of type {}", operand_type));
+        }
+        Ok(operand)
+    }
+
+    fn analyze_unary(&mut self, op: UnaryOperator, operand: Expression) -> Result<Expression, String> {
+        let (operand, result_type) = match op {
+            UnaryOperator::AddressOf => {
+                let operand = self.analyze_expr(operand)?;
+                if !is_lvalue(&operand) {
+                    return Err("Cannot take the address of an rvalue".to_string());
+                }
+                let pointer_type = Type::pointer_to(operand.expr_type().clone());
+                (operand, pointer_type)
+            }
+            UnaryOperator::Dereference => {
+                let operand = self.analyze_value(operand)?;
+                let Some(target) = operand.expr_type().pointee().cloned()
+                else { return Err(format!("Cannot dereference a value of type {}", operand.expr_type())) };
+                (operand, target)
+            }
+            UnaryOperator::LogicNot => (self.analyze_scalar(operand)?, Type::Int),
+            UnaryOperator::Negation | UnaryOperator::BitwiseNot => {
+                let operand = self.analyze_value(operand)?;
+                if !operand.expr_type().is_integer() {
+                    return Err(format!("Invalid operand of type {} to unary {}", operand.expr_type(), unary_symbol(op)));
+                }
+                let promoted = operand.expr_type().promote();
+                (convert(operand, &promoted), promoted)
+            }
+        };
+        Ok(Expression::typed(ExpressionKind::UnaryOperation(op, Box::new(operand)), result_type))
+    }
+
+    fn analyze_binary(&mut self, left: Expression, op: BinaryOperator, right: Expression) -> Result<Expression, String> {
+        let mut left = self.analyze_value(left)?;
+        let mut right = self.analyze_value(right)?;
+        let left_type = left.expr_type().clone();
+        let right_type = right.expr_type().clone();
+        let invalid = || format!("Invalid operands to binary {} ({} and {})", symbol(op), left_type, right_type);
+        let is_comparison = matches!(
+            op,
+            BinaryOperator::EQ | BinaryOperator::NEQ | BinaryOperator::LT
+                | BinaryOperator::GT | BinaryOperator::LE | BinaryOperator::GE
+        );
+        let result_type = match op {
+            BinaryOperator::LogicAnd | BinaryOperator::LogicOr => {
+                let scalar = |t: &Type| t.is_integer() || t.is_pointer();
+                if !scalar(&left_type) || !scalar(&right_type) {
+                    return Err(invalid());
+                }
+                Type::Int
+            }
+            BinaryOperator::Plus | BinaryOperator::Minus if left_type.is_pointer() || right_type.is_pointer() => {
+                check_pointer_arithmetic(&left_type)?;
+                check_pointer_arithmetic(&right_type)?;
+                match (op, left_type.is_pointer(), right_type.is_pointer()) {
+                    // ptrdiff_t
+                    (BinaryOperator::Minus, true, true) if left_type == right_type => Type::Long,
+                    (_, true, false) if right_type.is_integer() => left_type.clone(),
+                    (BinaryOperator::Plus, false, true) if left_type.is_integer() => right_type.clone(),
+                    _ => return Err(invalid()),
+                }
+            }
+            // Each operand of a shift is promoted on its own
+            BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+                if !left_type.is_integer() || !right_type.is_integer() {
+                    return Err(invalid());
+                }
+                left = convert(left, &left_type.promote());
+                right = convert(right, &right_type.promote());
+                left_type.promote()
+            }
+            _ if is_comparison && (left_type.is_pointer() || right_type.is_pointer()) => {
+                if left_type.is_pointer() && is_null_pointer_constant(&right) {
+                    right = convert(right, &left_type);
+                } else if right_type.is_pointer() && is_null_pointer_constant(&left) {
+                    left = convert(left, &right_type);
+                } else if left_type != right_type {
+                    return Err(invalid());
+                }
+                Type::Int
+            }
+            _ => {
+                if !left_type.is_integer() || !right_type.is_integer() {
+                    return Err(invalid());
+                }
+                let common_type = Type::common(&left_type, &right_type);
+                left = convert(left, &common_type);
+                right = convert(right, &common_type);
+                if is_comparison { Type::Int } else { common_type }
+            }
+        };
+        Ok(Expression::typed(ExpressionKind::BinaryOperation(Box::new(left), op, Box::new(right)), result_type))
+    }
+}
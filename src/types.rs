@@ -0,0 +1,287 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Char,
+    Short,
+    Int,
+    Long,
+    LongLong,
+    UnsignedChar,
+    UnsignedShort,
+    UnsignedInt,
+    UnsignedLong,
+    UnsignedLongLong,
+    Pointer(Box<Type>),
+    /// Element type and number of elements
+    Array(Box<Type>, i64),
+    /// A struct or union
+    Struct(Rc<StructType>),
+}
+
+/// A struct or union type. Every declaration that refers to the same struct
+/// shares one `StructType`, which is what makes two struct types the same.
+pub struct StructType {
+    /// Unions lay all their members out at offset zero
+    pub is_union: bool,
+    pub tag: Option<String>,
+    /// Member names and types as written in a specifier that defines the
+    /// struct, before semantic analysis resolves the tags they refer to
+    pub definition: Option<Vec<(String, Type)>>,
+    /// Set once the struct has been defined
+    layout: RefCell<Option<StructLayout>>,
+}
+
+struct StructLayout {
+    members: Vec<Member>,
+    size: i64,
+    align: i64,
+}
+
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    pub member_type: Type,
+    /// Offset in bytes from the start of the struct
+    pub offset: i64,
+}
+
+impl StructType {
+    pub fn new(is_union: bool, tag: Option<String>, definition: Option<Vec<(String, Type)>>) -> Self {
+        Self {
+            is_union,
+            tag,
+            definition,
+            layout: RefCell::new(None),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.layout.borrow().is_some()
+    }
+
+    /// Defines the struct, laying out its members as the System V ABI
+    /// does: each at the next offset aligned for its type, with the whole
+    /// struct padded to a multiple of its strictest member alignment. Union
+    /// members all start at offset zero and overlap.
+    pub fn complete(&self, members: Vec<(String, Type)>) {
+        let mut offset = 0;
+        let mut end = 0;
+        let mut align = 1;
+        let mut laid_out = Vec::new();
+        for (name, member_type) in members {
+            let member_align = member_type.align();
+            offset = (offset + member_align - 1) / member_align * member_align;
+            align = align.max(member_align);
+            let member_end = offset + member_type.size();
+            laid_out.push(Member { name, member_type, offset });
+            end = end.max(member_end);
+            if !self.is_union {
+                offset = member_end;
+            }
+        }
+        let size = (end + align - 1) / align * align;
+        *self.layout.borrow_mut() = Some(StructLayout { members: laid_out, size, align });
+    }
+
+    pub fn member(&self, name: &str) -> Option<Member> {
+        let layout = self.layout.borrow();
+        layout.as_ref()?.members.iter().find(|m| m.name == name).cloned()
+    }
+
+    fn size(&self) -> i64 {
+        self.layout.borrow().as_ref().map(|l| l.size)
+            .unwrap_or_else(|| panic!("{:?} is incomplete", self))
+    }
+
+    fn align(&self) -> i64 {
+        self.layout.borrow().as_ref().map(|l| l.align)
+            .unwrap_or_else(|| panic!("{:?} is incomplete", self))
+    }
+}
+
+impl PartialEq for StructType {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// Members can point back to the struct itself, so only the tag is shown
+impl fmt::Debug for StructType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.is_union { "union " } else { "struct " })?;
+        match &self.tag {
+            Some(tag) => f.write_str(tag),
+            None => f.write_str("<anonymous>"),
+        }
+    }
+}
+
+impl Type {
+    pub fn pointer_to(target: Type) -> Self {
+        Type::Pointer(Box::new(target))
+    }
+
+    /// Size in bytes
+    pub fn size(&self) -> i64 {
+        match self {
+            Type::Char | Type::UnsignedChar => 1,
+            Type::Short | Type::UnsignedShort => 2,
+            Type::Int | Type::UnsignedInt => 4,
+            Type::Long | Type::LongLong | Type::UnsignedLong | Type::UnsignedLongLong => 8,
+            Type::Pointer(_) => 8,
+            Type::Array(element, length) => element.size() * length,
+            Type::Struct(struct_type) => struct_type.size(),
+        }
+    }
+
+    pub fn align(&self) -> i64 {
+        match self {
+            Type::Array(element, _) => element.align(),
+            Type::Struct(struct_type) => struct_type.align(),
+            _ => self.size(),
+        }
+    }
+
+    /// The type an expression of this type has when used as a value: arrays
+    /// become pointers to their first element.
+    pub fn decay(&self) -> Type {
+        match self {
+            Type::Array(element, _) => Type::pointer_to((**element).clone()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Whether the size of the type is known
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Type::Array(element, _) => element.is_complete(),
+            Type::Struct(struct_type) => struct_type.is_complete(),
+            _ => true,
+        }
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Type::Pointer(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_signed() || matches!(
+            self,
+            Type::UnsignedChar | Type::UnsignedShort | Type::UnsignedInt
+                | Type::UnsignedLong | Type::UnsignedLongLong
+        )
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Type::Char | Type::Short | Type::Int | Type::Long | Type::LongLong)
+    }
+
+    /// Wraps an integer value around to the range of this type, the way a
+    /// conversion to it does.
+    pub fn wrap(&self, value: i64) -> i64 {
+        match (self.size(), self.is_signed()) {
+            (1, true) => value as i8 as i64,
+            (1, false) => value as u8 as i64,
+            (2, true) => value as i16 as i64,
+            (2, false) => value as u16 as i64,
+            (4, true) => value as i32 as i64,
+            (4, false) => value as u32 as i64,
+            _ => value,
+        }
+    }
+
+    /// The unsigned integer type with the same rank
+    pub fn to_unsigned(&self) -> Type {
+        match self {
+            Type::Char => Type::UnsignedChar,
+            Type::Short => Type::UnsignedShort,
+            Type::Int => Type::UnsignedInt,
+            Type::Long => Type::UnsignedLong,
+            Type::LongLong => Type::UnsignedLongLong,
+            _ if self.is_integer() => self.clone(),
+            _ => panic!("{:?} is not an integer type", self),
+        }
+    }
+
+    /// The type a pointer points to, or the element type of an array
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Pointer(target) | Type::Array(target, _) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Integer conversion rank, used to find the common type of two integers
+    fn rank(&self) -> u8 {
+        match self {
+            Type::Char | Type::UnsignedChar => 1,
+            Type::Short | Type::UnsignedShort => 2,
+            Type::Int | Type::UnsignedInt => 3,
+            Type::Long | Type::UnsignedLong => 4,
+            Type::LongLong | Type::UnsignedLongLong => 5,
+            _ => panic!("{:?} is not an integer type", self),
+        }
+    }
+
+    /// Integer promotion: anything smaller than `int` is computed as `int`
+    pub fn promote(&self) -> Type {
+        match self {
+            Type::Char | Type::Short | Type::UnsignedChar | Type::UnsignedShort => Type::Int,
+            _ => self.clone(),
+        }
+    }
+
+    /// The type both operands of an arithmetic operator are converted to,
+    /// following the usual arithmetic conversions.
+    pub fn common(a: &Type, b: &Type) -> Type {
+        let a = a.promote();
+        let b = b.promote();
+        if a == b {
+            return a;
+        }
+        if a.is_signed() == b.is_signed() {
+            return if a.rank() >= b.rank() { a } else { b };
+        }
+        let (signed, unsigned) = if a.is_signed() { (a, b) } else { (b, a) };
+        if unsigned.rank() >= signed.rank() {
+            unsigned
+        } else if signed.size() > unsigned.size() {
+            // Every value of the unsigned type fits in the signed one
+            signed
+        } else {
+            signed.to_unsigned()
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Char => f.write_str("char"),
+            Type::Short => f.write_str("short"),
+            Type::Int => f.write_str("int"),
+            Type::Long => f.write_str("long"),
+            Type::LongLong => f.write_str("long long"),
+            Type::UnsignedChar => f.write_str("unsigned char"),
+            Type::UnsignedShort => f.write_str("unsigned short"),
+            Type::UnsignedInt => f.write_str("unsigned int"),
+            Type::UnsignedLong => f.write_str("unsigned long"),
+            Type::UnsignedLongLong => f.write_str("unsigned long long"),
+            Type::Pointer(target) => write!(f, "{} *", target),
+            Type::Struct(struct_type) => write!(f, "{:?}", struct_type),
+            Type::Array(..) => {
+                // Dimensions are written outermost first, as in the declaration
+                let mut dimensions = String::new();
+                let mut element = self;
+                while let Type::Array(inner, length) = element {
+                    dimensions.push_str(&format!("[{}]", length));
+                    element = inner;
+                }
+                write!(f, "{}{}", element, dimensions)
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Checks that invalid programs are rejected by semantic analysis with a
+//! diagnostic and exit status 1, rather than reaching code generation.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Compiles each `(source, error)` case and checks that unicc fails with
+/// `error: ` followed by the expected message. `name` keeps the sources of
+/// different tests apart.
+fn check_errors(name: &str, cases: &[(&str, &str)]) {
+    let build = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("diagnostics");
+    std::fs::create_dir_all(&build).unwrap();
+    for (index, (source, error)) in cases.iter().enumerate() {
+        let path = build.join(format!("{}{}.c", name, index));
+        std::fs::write(&path, source).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_unicc")).arg(&path).output().unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "unicc did not reject {:?}:\n{}", source, stderr);
+        let expected = format!("error: {}", error);
+        assert!(
+            stderr.lines().any(|line| line == expected),
+            "{:?}: expected {:?}, got:\n{}",
+            source,
+            expected,
+            stderr
+        );
+    }
+}
+
+#[test]
+fn declarations() {
+    check_errors("declarations", &[
+        ("int main(void) { int x; int x; return 0; }", "In function main: Redeclaration of x"),
+        ("int x; static int x;", "Static declaration of x follows non-static declaration"),
+        ("static int x; int x;", "Non-static declaration of x follows static declaration"),
+        ("int g = 1; int g = 2;", "Redefinition of g"),
+        ("int g; long g;", "Conflicting types for g"),
+        ("int f(void); long f(void);", "In function f: Conflicting types for function f"),
+        ("int f(void) { return 0; } int f(void) { return 1; }", "In function f: Redefinition of function f"),
+        ("int f(void); int f;", "f redeclared as a different kind of symbol"),
+        (
+            "int main(void) { extern int x = 1; return 0; }",
+            "In function main: Block scope extern declaration of x has an initializer",
+        ),
+        ("struct s; struct s v;", "Variable v has incomplete type struct s"),
+        ("struct s { int a; int a; };", "Duplicate member a in struct s"),
+    ]);
+}
+
+#[test]
+fn expressions() {
+    check_errors("expressions", &[
+        ("int main(void) { return y; }", "In function main: Use of undeclared identifier y"),
+        ("int main(void) { return f(); }", "In function main: Implicit declaration of function f"),
+        (
+            "int f(int a); int main(void) { return f(1, 2); }",
+            "In function main: Function f expects 1 arguments, got 2",
+        ),
+        ("int main(void) { 1 = 2; return 0; }", "In function main: Expression is not assignable"),
+        (
+            "int main(void) { int a[2]; int b[2]; a = b; return 0; }",
+            "In function main: Cannot assign to an array of type int[2]",
+        ),
+        (
+            "int main(void) { int *p; p = 1; return 0; }",
+            "In function main: Incompatible types: cannot convert int to int *",
+        ),
+        (
+            "int main(void) { long *p; return -p == 0; }",
+            "In function main: Invalid operand of type long * to unary -",
+        ),
+        ("int main(void) { return *1; }", "In function main: Cannot dereference a value of type int"),
+        ("int main(void) { return &1 == 0; }", "In function main: Cannot take the address of an rvalue"),
+        (
+            "int main(void) { int x; return x.a; }",
+            "In function main: Member reference base type int is not a struct or union",
+        ),
+        (
+            "struct s; int f(struct s *p) { return p + 1 == p; }",
+            "In function f: Arithmetic on pointer to incomplete type struct s",
+        ),
+        (
+            "struct s; int f(struct s *p, struct s *q) { *p = *q; return 0; }",
+            "In function f: Cannot assign to a value of incomplete type struct s",
+        ),
+        (
+            "int f(void); int main(void) { int x = f; return x; }",
+            "In function main: Function f used as a value, but function pointers are not supported",
+        ),
+    ]);
+}
+
+#[test]
+fn statements() {
+    check_errors("statements", &[
+        (
+            "int main(void) { break; return 0; }",
+            "In function main: break statement not within loop or switch",
+        ),
+        (
+            "int main(void) { switch (1) { case 0: continue; } return 0; }",
+            "In function main: continue statement not within a loop",
+        ),
+        (
+            "int main(void) { case 1: return 0; }",
+            "In function main: case label not within a switch statement",
+        ),
+        (
+            "int main(void) { int x = 1; switch (x) { case x: return 0; } return 1; }",
+            "In function main: case label does not reduce to an integer constant",
+        ),
+        (
+            "int main(void) { switch (1) { case 1: case 2 - 1: return 0; } return 1; }",
+            "In function main: Duplicate case value 1",
+        ),
+        (
+            "int main(void) { switch (1) { default: default: return 0; } }",
+            "In function main: Multiple default labels in one switch",
+        ),
+        ("int main(void) { a: a: return 0; }", "In function main: Duplicate label a"),
+        ("int main(void) { goto out; return 0; }", "In function main: Label out used but not defined"),
+    ]);
+}
@@ -0,0 +1,65 @@
+//! Compiles the C programs in `tests/programs` with unicc and with the
+//! system C compiler, runs both, and checks that they print the same output
+//! and exit with the same status.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(command: &mut Command) -> Output {
+    let output = command.output().unwrap_or_else(|error| panic!("Failed to run {:?}: {}", command, error));
+    if !output.status.success() && output.status.code().is_none() {
+        panic!("{:?} was killed: {}", command, output.status);
+    }
+    output
+}
+
+/// Builds `source` into `executable` with the system C compiler
+fn compile(source: &Path, executable: &Path) {
+    let output = run(Command::new("cc").arg("-w").arg("-o").arg(executable).arg(source));
+    assert!(output.status.success(), "cc failed on {}:\n{}", source.display(), String::from_utf8_lossy(&output.stderr));
+}
+
+fn check_program(name: &str) {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs").join(format!("{}.c", name));
+    let build = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+
+    let output = run(Command::new(env!("CARGO_BIN_EXE_unicc")).arg(&source));
+    assert!(output.status.success(), "unicc failed on {}:\n{}", name, String::from_utf8_lossy(&output.stderr));
+    let assembly = build.join(format!("{}.s", name));
+    std::fs::write(&assembly, &output.stdout).unwrap();
+    let compiled = build.join(format!("{}.unicc", name));
+    compile(&assembly, &compiled);
+
+    let reference = build.join(format!("{}.cc", name));
+    compile(&source, &reference);
+
+    let expected = run(&mut Command::new(&reference));
+    let actual = run(&mut Command::new(&compiled));
+    assert_eq!(String::from_utf8_lossy(&actual.stdout), String::from_utf8_lossy(&expected.stdout));
+    assert_eq!(actual.status.code(), expected.status.code());
+}
+
+/// Switches just below and at each change of lowering strategy: fewer than
+/// four cases are compared in turn, and more go through a jump table when
+/// at least a third of its entries are cases, or a binary search otherwise.
+#[test]
+fn switch_lowering() {
+    check_program("switch");
+}
+
+/// Remainders by constant divisors of each width, signed and unsigned,
+/// which are lowered without a division up to 32 bits and for powers of
+/// two. The most negative value modulo -1 must give 0 rather than trap
+/// like `idiv` does, and signed `char` and `short` dividends modulo an
+/// unsigned divisor are converted to `unsigned int` first.
+#[test]
+fn remainder_by_constant() {
+    check_program("remainder");
+}
+
+/// Signed `char` and `short` operands mixed with `unsigned int` ones, which
+/// convert to large unsigned values when negative, as in a string hash
+#[test]
+fn narrow_signed_with_unsigned() {
+    check_program("unsigned");
+}
@@ -0,0 +1,72 @@
+int printf();
+
+int signed_char(signed char x) {
+    return printf("%d %d %d %d %d %d %d %d\n", x % 1, x % -1, x % 2, x % -2, x % 3, x % -7, x % 64, x % -128);
+}
+
+int unsigned_char(unsigned char x) {
+    return printf("%d %d %d %d %d %d\n", x % 1, x % 2, x % 3, x % 10, x % 128, x % 255);
+}
+
+int signed_short(short x) {
+    return printf("%d %d %d %d %d %d %d %d\n", x % 1, x % -1, x % 2, x % -8, x % 7, x % -10, x % 1024,
+        x % (-32767 - 1));
+}
+
+int narrow_by_unsigned(signed char c, short s) {
+    return printf("%u %u %u %u %u %u\n", c % 3u, c % 7u, c % 4294967295u, s % 3u, s % 7u, s % 4294967295u);
+}
+
+int unsigned_short(unsigned short x) {
+    return printf("%d %d %d %d %d %d\n", x % 1, x % 2, x % 3, x % 1000, x % 32768, x % 65535);
+}
+
+int signed_int(int x) {
+    return printf("%d %d %d %d %d %d %d %d %d %d %d\n", x % 1, x % -1, x % 2, x % -2, x % 16, x % 3, x % -7,
+        x % 1000003, x % 2147483647, x % (-2147483647 - 1), x % -1073741824);
+}
+
+int unsigned_int(unsigned x) {
+    return printf("%u %u %u %u %u %u %u %u\n", x % 1, x % 2, x % 3, x % 7, x % 641, x % 2147483648u,
+        x % 2147483649u, x % 4294967295u);
+}
+
+int signed_long(long x) {
+    return printf("%ld %ld %ld %ld %ld %ld %ld %ld %ld\n", x % 1, x % -1, x % 2, x % -4096, x % 7, x % -10,
+        x % 4294967296, x % 9223372036854775807, x % (-9223372036854775807 - 1));
+}
+
+int unsigned_long(unsigned long x) {
+    return printf("%lu %lu %lu %lu %lu %lu\n", x % 1, x % 2, x % 3, x % 4294967296, x % 9223372036854775808ul,
+        x % 18446744073709551615ul);
+}
+
+int main() {
+    int ints[12];
+    ints[0] = 0; ints[1] = 1; ints[2] = -1; ints[3] = 7; ints[4] = -7; ints[5] = 127; ints[6] = -128;
+    ints[7] = 32767; ints[8] = -32767 - 1; ints[9] = 2147483647; ints[10] = -2147483647 - 1;
+    ints[11] = 123456789;
+    for (int i = 0; i < 12; i++) {
+        signed_char(ints[i]);
+        unsigned_char(ints[i]);
+        signed_short(ints[i]);
+        narrow_by_unsigned(ints[i], ints[i]);
+        unsigned_short(ints[i]);
+        signed_int(ints[i]);
+        unsigned_int(ints[i]);
+    }
+    long longs[9];
+    longs[0] = 0; longs[1] = 1; longs[2] = -1; longs[3] = 5000000001; longs[4] = -5000000001;
+    longs[5] = 4294967295; longs[6] = 9223372036854775807; longs[7] = -9223372036854775807 - 1;
+    longs[8] = -4096;
+    for (int i = 0; i < 9; i++) {
+        signed_long(longs[i]);
+        unsigned_long(longs[i]);
+    }
+    int r = -2147483647 - 1;
+    r %= -1;
+    long s = 17;
+    s %= -5;
+    printf("%d %ld\n", r, s);
+    return 47 % 10;
+}
@@ -0,0 +1,140 @@
+int printf();
+
+int three_cases(int x) {
+    switch (x) {
+    case -1: return 1;
+    case 0: return 2;
+    case 1000: return 3;
+    }
+    return 0;
+}
+
+int four_dense(int x) {
+    switch (x) {
+    case 0: return 1;
+    case 1: return 2;
+    case 2: return 3;
+    case 3: return 4;
+    default: return -1;
+    }
+}
+
+int table_at_threshold(int x) {
+    int r = 0;
+    switch (x) {
+    case 0: r = r + 1;
+    case 3: r = r + 2;
+    case 7: r = r + 4; break;
+    case 11: r = 8; break;
+    }
+    return r;
+}
+
+int search_past_threshold(int x) {
+    switch (x) {
+    case 0: return 1;
+    case 4: return 2;
+    case 8: return 3;
+    case 12: return 4;
+    default: return -1;
+    }
+}
+
+int negative_table(int x) {
+    switch (x) {
+    case -2: return 1;
+    case -1: return 2;
+    case 0: return 3;
+    case 1: return 4;
+    default: return 0;
+    }
+}
+
+unsigned unsigned_search(unsigned x) {
+    switch (x) {
+    case 1: return 1;
+    case 1000: return 2;
+    case 2147483648u: return 3;
+    case 4294967295u: return 4;
+    case 70000: return 5;
+    }
+    return 0;
+}
+
+long wide_table(long x) {
+    switch (x) {
+    case 5000000000: return 1;
+    case 5000000001: return 2;
+    case 5000000003: return 3;
+    case 5000000004: return 4;
+    default: return 0;
+    }
+}
+
+long long_search(long x) {
+    switch (x) {
+    case -9223372036854775807 - 1: return 1;
+    case -5: return 2;
+    case 0: return 3;
+    case 100000: return 4;
+    case 9223372036854775807: return 5;
+    default: return -1;
+    }
+}
+
+int char_switch(char c) {
+    switch (c) {
+    case 'a': return 1;
+    case 'b': return 2;
+    case 'c': return 3;
+    case 'd': return 4;
+    case -56: return 5;
+    }
+    return 0;
+}
+
+int nested(int x, int y) {
+    int r = 0;
+    switch (x) {
+    case 0:
+        switch (y) {
+        case 0: r = 1; break;
+        case 1: r = 2; break;
+        case 2: r = 3; break;
+        case 3: r = 4; break;
+        default: r = 5;
+        }
+        break;
+    case 1: r = 6;
+    default: r = r + 10;
+    }
+    return r;
+}
+
+int main() {
+    for (int x = -3; x <= 14; x++) {
+        printf("%d: %d %d %d %d %d\n", x, three_cases(x), four_dense(x), table_at_threshold(x),
+            search_past_threshold(x), negative_table(x));
+    }
+    printf("%d %d %d\n", three_cases(1000), three_cases(999), three_cases(-2147483647 - 1));
+    unsigned u[9];
+    u[0] = 0; u[1] = 1; u[2] = 999; u[3] = 1000; u[4] = 70000; u[5] = 2147483647;
+    u[6] = 2147483648u; u[7] = 4294967294u; u[8] = 4294967295u;
+    for (int i = 0; i < 9; i++) {
+        printf("%u ", unsigned_search(u[i]));
+    }
+    printf("\n");
+    for (long l = 4999999998; l <= 5000000006; l++) {
+        printf("%ld ", wide_table(l));
+    }
+    printf("%ld %ld\n", wide_table(-5000000000), wide_table(705032704));
+    printf("%ld %ld %ld %ld %ld %ld %ld\n", long_search(-9223372036854775807 - 1), long_search(-5),
+        long_search(0), long_search(100000), long_search(9223372036854775807), long_search(1),
+        long_search(-9223372036854775807));
+    printf("%d %d %d %d %d\n", char_switch('a'), char_switch('d'), char_switch('e'), char_switch(200),
+        char_switch(-56));
+    for (int y = -1; y <= 4; y++) {
+        printf("%d %d %d\n", nested(0, y), nested(1, y), nested(2, y));
+    }
+    return four_dense(2);
+}
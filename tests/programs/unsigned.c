@@ -0,0 +1,56 @@
+int printf();
+
+unsigned hash(char *s) {
+    unsigned h = 5381;
+    while (*s) {
+        h = h * 33 ^ *s;
+        s++;
+    }
+    return h;
+}
+
+int compare(short s, signed char c, unsigned u) {
+    return printf("%d %d %d %d %d %d %d %d\n", s == u, u == s, c == u, u == c, s < u, u < s, c <= u, u >= c);
+}
+
+int arithmetic(short s, signed char c, unsigned u) {
+    return printf("%u %u %u %u %u %u %u %u %u %u\n", s + u, u - c, s * u, s / 2u, u / s, c / 3u, u % c,
+        s % u, s >> 3 == u >> 3, c + 0u >> 1);
+}
+
+int remainders(short s, signed char c) {
+    return printf("%u %u %u %u %u %u\n", s % 3u, s % 7u, s % 4294967295u, c % 3u, c % 7u, c % 4294967295u);
+}
+
+int compound(short s, signed char c) {
+    unsigned u = 10;
+    u += s;
+    unsigned v = 4294967295u;
+    v /= c;
+    unsigned w = 1000;
+    w %= c;
+    unsigned x = 100;
+    x -= c;
+    return printf("%u %u %u %u\n", u, v, w, x);
+}
+
+int main() {
+    short shorts[5];
+    shorts[0] = -1; shorts[1] = -32767 - 1; shorts[2] = 32767; shorts[3] = -2; shorts[4] = 5;
+    signed char chars[5];
+    chars[0] = -2; chars[1] = -128; chars[2] = 127; chars[3] = -1; chars[4] = 3;
+    unsigned units[5];
+    units[0] = 4294967295u; units[1] = 4294967294u; units[2] = 2147483648u; units[3] = 1; units[4] = 65535;
+    for (int i = 0; i < 5; i++) {
+        for (int j = 0; j < 5; j++) {
+            compare(shorts[i], chars[i], units[j]);
+            arithmetic(shorts[i], chars[i], units[j]);
+        }
+        remainders(shorts[i], chars[i]);
+        compound(shorts[i], chars[i]);
+    }
+    signed char low = -128;
+    printf("%d %d\n", -128 < 4294967295u, low < 4294967295u);
+    printf("%u %u\n", hash("hello, world"), hash("\xe9t\xe9"));
+    return 0;
+}